@@ -1,9 +1,26 @@
+use crate::app::backends::select_backend;
+use crate::app::inhibitors::count_active_inhibitors;
+use crate::app::manager::{emit_state_changed, serve};
+use crate::app::media_player::{clear_player_proxies, describe_now_playing, get_player_name, list_players, list_players_among, pause_all_players, NowPlaying, PlayerFilter, PlayerInfo, PlayerStatus, SharedPlayerFilter};
 use crate::app::monitor::channel::AppChannel;
+use crate::app::notifications::{NotificationCategory, NotificationSettings, Notifier};
 use crate::app::monitor::media_monitor::MediaMonitor;
 use crate::app::monitor::playback_monitor::PlaybackMonitor;
+use crate::app::schedule::Schedule;
 use crate::app::screensaver::ScreensaverState;
-use std::sync::Arc;
-use zbus::Connection;
+use crate::config::Config;
+use crate::exit_status::ExitStatus;
+use crate::global_constants::{MONITOR_RESTART_MAX_BACKOFF, MONITOR_RESTART_MIN_BACKOFF, REFRESH_CHANNEL_CAPACITY};
+use crate::utils::{env_flag, player_display_name};
+use futures::future::join_all;
+use futures::FutureExt;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use zbus::fdo::DBusProxy;
+use zbus::{connection, Connection};
 
 // Type alias for a signal that indicates that the list of media players has changes
 pub type MediaPlayerListChangeSignal = ();
@@ -14,13 +31,38 @@ pub type SystemTrayRefreshScreensaverSignal = ();
 // Type alias for a signal that is sent to the UI to request an icon refresh
 pub type UiRefreshSignal = ();
 
+// A snapshot of the state of the blocker, for displaying to the user
+#[derive(Clone, Debug, Default)]
+pub struct StatusSnapshot {
+    /// The media that is currently keeping the screen awake (None if not blocking)
+    pub now_playing: Option<NowPlaying>,
+
+    /// The media players the last update found keeping the screen awake (empty if it did not query them)
+    pub playing_players: Vec<PlayerStatus>,
+
+    /// The names of the media players that an inhibit is held for, to display to the user
+    pub blocking_players: Vec<String>,
+
+    /// The number of idle inhibitors active on the system, including ours (None if unknown)
+    pub active_inhibitors: Option<usize>,
+}
+
 pub struct Application {
-    /// Connection to the D-Bus session
-    connection: Connection,
+    /// Connection to the D-Bus session (replaced if the session bus drops)
+    connection: RwLock<Connection>,
+
+    /// Connection to the D-Bus system bus (None if unavailable)
+    system_connection: Option<Connection>,
 
     /// The blocked/unblocked state of the screensaver
     screensaver: Arc<ScreensaverState>,
 
+    /// How the screen is kept awake, used to select a backend again if none was available at startup
+    keep_awake_mode: String,
+
+    /// Which media players are allowed to keep the screen awake (changed by the tray at runtime)
+    player_filter: SharedPlayerFilter,
+
     /// The channel for the system tray
     tray_channel: AppChannel<SystemTrayRefreshScreensaverSignal>,
 
@@ -29,31 +71,150 @@ pub struct Application {
 
     /// The channel for the UI refresh notification
     ui_channel: AppChannel<UiRefreshSignal>,
+
+    /// Sends desktop notifications for the categories the user enabled
+    notifier: Arc<Notifier>,
+
+    /// Exit the process when a monitor crashes, so a supervisor can restart it
+    exit_on_failure: bool,
+
+    /// How long to gather bursts of player signals into one update
+    signal_debounce: Duration,
+
+    /// How long to wait for a reply to a D-Bus call, so a hung player cannot stall the monitors
+    dbus_timeout: Duration,
+
+    /// How often to re-check the players, in case a signal was missed (None to never re-check)
+    poll_interval: Option<Duration>,
+
+    /// The daily windows the screen can be kept awake in
+    schedule: Schedule,
+
+    /// The battery charge in percent below which the screen is not kept awake while on battery (None if never)
+    battery_threshold: Option<f64>,
+
+    /// The last snapshot of the state of the blocker
+    status: Mutex<StatusSnapshot>,
+
+    /// When the blocker is re-enabled after being snoozed (None if not snoozed)
+    snooze_deadline: Mutex<Option<Instant>>,
+
+    /// Indicate if the monitors are running, cleared when a monitor loop fails
+    healthy: AtomicBool,
+
+    /// The number of media players being tracked, as of the last time the players were listed
+    tracked_players: AtomicUsize,
+
+    /// Every media player on the D-Bus session, including those not tracked, as of the last time the players were
+    /// listed (None until they are first listed)
+    player_names: Mutex<Option<Vec<String>>>,
 }
 
 impl Application {
-    pub async fn new() -> anyhow::Result<Self> {
+    pub async fn new(config: &Config) -> anyhow::Result<Self> {
         // Establish a connection to the D-Bus session
-        let conn = Connection::session().await?;
+        let dbus_timeout = Duration::from_millis(config.dbus_timeout_ms);
+        let conn = connection::Builder::session()?.method_timeout(dbus_timeout).build().await?;
 
+        // Establish a connection to the D-Bus system bus, which is optional
+        let system_conn = match Self::connect_system(dbus_timeout).await {
+            Ok(system_conn) => Some(system_conn),
+            Err(e) => {
+                log::warn!("[SYSTEM] Unable to connect to the D-Bus system bus: {}", e);
+                None
+            }
+        };
+
+        // Select the service used to keep the screen awake (None if no service is available)
+        let backend = select_backend(&conn, system_conn.as_ref(), &config.keep_awake_mode).await;
+
+        // Read which media players the user wants tracked
+        let player_filter = Arc::new(RwLock::new(PlayerFilter::from_config(config)));
+
+        // Track the state of the screensaver, querying the players the user wants tracked
+        let screensaver = ScreensaverState::new(backend, system_conn.clone(), config, player_filter.clone());
+        Ok(Self::from_parts(config, conn, system_conn, screensaver, player_filter))
+    }
+
+    pub fn from_parts(
+        config: &Config,
+        conn: Connection,
+        system_conn: Option<Connection>,
+        screensaver: ScreensaverState,
+        player_filter: SharedPlayerFilter,
+    ) -> Self {
         // Construct the ApplicationState instance
-        Ok(Self {
-            connection: conn,
-            screensaver: Arc::new(ScreensaverState::new()),
-            tray_channel: AppChannel::new(),
+        Self {
+            connection: RwLock::new(conn),
+            screensaver: Arc::new(screensaver),
+            system_connection: system_conn,
+            keep_awake_mode: config.keep_awake_mode.clone(),
+            player_filter,
+            tray_channel: AppChannel::bounded(REFRESH_CHANNEL_CAPACITY),
+            // Left unbounded, as it only receives a signal when a media player is added or removed
             media_channel: AppChannel::new(),
-            ui_channel: AppChannel::new(),
-        })
+            ui_channel: AppChannel::bounded(REFRESH_CHANNEL_CAPACITY),
+            notifier: Arc::new(Notifier::new(NotificationSettings::from_config(config))),
+            exit_on_failure: env_flag("MEDIABLOCKER_EXIT_ON_FAILURE").unwrap_or(false),
+            signal_debounce: Duration::from_millis(config.signal_debounce_ms),
+            dbus_timeout: Duration::from_millis(config.dbus_timeout_ms),
+            poll_interval: Some(Duration::from_secs(config.poll_interval_secs)).filter(|interval| !interval.is_zero()),
+            schedule: Schedule::parse(&config.schedule),
+            battery_threshold: Some(config.disable_below_battery_percent)
+                .filter(|percent| *percent > 0)
+                .map(f64::from),
+            status: Mutex::new(StatusSnapshot::default()),
+            snooze_deadline: Mutex::new(None),
+            healthy: AtomicBool::new(true),
+            tracked_players: AtomicUsize::new(0),
+            player_names: Mutex::new(None),
+        }
     }
 
-    pub fn get_connection(&self) -> &Connection {
-        &self.connection
+    pub fn get_connection(&self) -> Connection {
+        // Connections are reference counted, so the clone shares the underlying connection
+        self.connection.read().unwrap().clone()
     }
 
     pub fn get_screensaver(&self) -> &Arc<ScreensaverState> {
         &self.screensaver
     }
 
+    pub fn get_player_filter(&self) -> PlayerFilter {
+        self.player_filter.read().unwrap().clone()
+    }
+
+    pub fn get_signal_debounce(&self) -> Duration {
+        self.signal_debounce
+    }
+
+    pub fn get_poll_interval(&self) -> Option<Duration> {
+        self.poll_interval
+    }
+
+    pub fn get_schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    pub fn get_battery_threshold(&self) -> Option<f64> {
+        self.battery_threshold
+    }
+
+    pub async fn recheck_backend(&self) -> bool {
+        // Look for a supported service again, in case one has started since the last check
+        let conn = self.get_connection();
+        let Some(backend) = select_backend(&conn, self.get_system_connection(), &self.keep_awake_mode).await else {
+            log::debug!("[SCREENSAVER] Still no inhibit backend available");
+            return false;
+        };
+        self.screensaver.set_backend(backend);
+        true
+    }
+
+    pub fn get_system_connection(&self) -> Option<&Connection> {
+        self.system_connection.as_ref()
+    }
+
     pub fn get_tray_channel(&self) -> &AppChannel<SystemTrayRefreshScreensaverSignal> {
         &self.tray_channel
     }
@@ -66,29 +227,480 @@ impl Application {
         &self.ui_channel
     }
 
+    pub fn get_status(&self) -> StatusSnapshot {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::SeqCst)
+    }
+
+    pub async fn set_healthy(&self, healthy: bool) {
+        // Only refresh the UI when the health has changed
+        if self.healthy.swap(healthy, Ordering::SeqCst) == healthy {
+            return;
+        }
+
+        // Request the UI to refresh, so the icon shows the problem
+        if let Err(e) = self.ui_channel.request_refresh() {
+            log::error!("[SYSTEM] Failed to request a UI refresh: {}", e);
+        }
+    }
+
+    pub fn get_snooze_deadline(&self) -> Option<Instant> {
+        *self.snooze_deadline.lock().unwrap()
+    }
+
+    pub fn cancel_snooze(&self) {
+        // Clearing the deadline stops the running timer from re-enabling the blocker
+        if self.snooze_deadline.lock().unwrap().take().is_some() {
+            log::info!("[SYSTEM] Snooze cancelled");
+        }
+    }
+
+    pub async fn snooze(self: Arc<Self>, duration: Duration) -> anyhow::Result<()> {
+        // Replace any previous snooze, so only the latest timer re-enables the blocker
+        let deadline = Instant::now() + duration;
+        *self.snooze_deadline.lock().unwrap() = Some(deadline);
+        log::info!("[SYSTEM] Snoozing the blocker for {} minutes", duration.as_secs() / 60);
+
+        // Disable the blocker, and release the inhibit straight away
+        self.screensaver.disallow_updates();
+        self.tray_channel.request_refresh()?;
+
+        // Re-enable the blocker once the snooze has elapsed, unless it was replaced or cancelled
+        async_std::task::spawn(async move {
+            async_std::task::sleep(duration).await;
+            if self.get_snooze_deadline() != Some(deadline) {
+                return;
+            }
+
+            *self.snooze_deadline.lock().unwrap() = None;
+            self.screensaver.allow_updates();
+            log::info!("[SYSTEM] Snooze elapsed, the blocker is enabled");
+            if let Err(e) = self.tray_channel.request_refresh() {
+                log::error!("[SYSTEM] Failed to send signal to worker: {}", e);
+            }
+        });
+
+        Ok(())
+    }
+
+    pub async fn set_enabled(&self, enabled: bool) -> anyhow::Result<()> {
+        // Enabling or disabling the blocker directly ends any snooze
+        self.cancel_snooze();
+        if enabled {
+            self.screensaver.allow_updates();
+        } else {
+            self.screensaver.disallow_updates();
+        }
+
+        // Re-evaluate the players, which also refreshes the tray so its checkbox stays in sync
+        self.tray_channel.request_refresh()?;
+        Ok(())
+    }
+
+    pub async fn set_manual_hold(&self, hold: bool) -> anyhow::Result<()> {
+        // Take or release the inhibit held regardless of the media
+        self.screensaver.set_manual_hold(&self.get_connection(), hold).await?;
+
+        // Re-evaluate the players, which also refreshes the tray to show the hold
+        self.tray_channel.request_refresh()?;
+        Ok(())
+    }
+
+    pub async fn list_players(&self) -> anyhow::Result<Vec<PlayerInfo>> {
+        // List every media player on the D-Bus session with its playback status, reusing the names and statuses
+        // from the last update rather than listing the players again
+        let conn = self.get_connection();
+        let filter = self.get_player_filter();
+        let Some(names) = self.player_names.lock().unwrap().clone() else {
+            return list_players(&conn, &filter).await;
+        };
+        let playing = self.get_status().playing_players;
+        Ok(list_players_among(&conn, names, &filter, &playing).await)
+    }
+
+    pub fn set_player_names(&self, names: Vec<String>) {
+        *self.player_names.lock().unwrap() = Some(names);
+    }
+
+    pub async fn set_player_tracked(&self, player: &str, tracked: bool) -> anyhow::Result<()> {
+        // Update the filter used by the monitors, keeping a copy to save
+        let filter = {
+            let mut filter = self.player_filter.write().unwrap();
+            filter.set_tracked(player, tracked);
+            filter.clone()
+        };
+        log::info!(
+            "[SYSTEM] {} is now {}",
+            player_display_name(player),
+            if tracked { "tracked" } else { "ignored" }
+        );
+
+        // Save the lists to the config, so the choice is kept across restarts
+        Config::update(|config| {
+            config.allowlist = filter.allowlist;
+            config.denylist = filter.denylist;
+        })?;
+
+        // Re-evaluate the players, as the player may be the one keeping the screen awake
+        self.tray_channel.request_refresh()?;
+
+        Ok(())
+    }
+
+    pub fn reload_players(&self) -> anyhow::Result<()> {
+        // Probe every player again, in case one that failed to answer before is now working
+        clear_player_proxies();
+        self.screensaver.invalidate_media_cache();
+
+        // Ask the playback monitor to list the players again, as if MediaMonitor had detected a change
+        log::info!("[SYSTEM] Rediscovering the media players");
+        self.media_channel.request_refresh()
+    }
+
+    pub fn reload_config(&self) -> anyhow::Result<Config> {
+        // Read the config again, so edits made to the file since startup are picked up
+        let config = Config::load()?;
+
+        // Apply the filter and inhibit settings, the other settings take effect on the next start
+        *self.player_filter.write().unwrap() = PlayerFilter::from_config(&config);
+        self.screensaver.apply_config(&config);
+        self.notifier.apply_settings(NotificationSettings::from_config(&config));
+        log::info!("[SYSTEM] Reloaded the config, settings that cannot change while running apply on restart");
+
+        // Re-evaluate the players, as the filter may have changed which ones keep the screen awake
+        self.screensaver.invalidate_media_cache();
+        self.tray_channel.request_refresh()?;
+
+        Ok(config)
+    }
+
+    pub async fn refresh_status(&self, playing_players: Vec<PlayerStatus>) {
+        // Only look up the media while it is keeping the screen awake, preferring a player an inhibit is held for
+        let blocking = self.screensaver.blocking_players();
+        let now_playing = match playing_players.iter().find(|player| blocking.contains(&player.name)) {
+            Some(player) if self.screensaver.is_blocked() => {
+                Some(describe_now_playing(&self.get_connection(), player).await)
+            }
+            _ => None,
+        };
+
+        // Count every idle inhibitor on the system, so other apps keeping the screen awake are visible
+        let active_inhibitors = count_active_inhibitors(
+            &self.get_connection(),
+            self.system_connection.as_ref(),
+            self.screensaver.uses_kde_quirks(),
+        )
+        .await;
+
+        // List the media players keeping the screen awake by the names shown to the user, which the update has
+        // already read for the players that are still playing
+        let conn = self.get_connection();
+        let blocking_players = join_all(blocking.iter().map(|name| {
+            let known = playing_players.iter().find(|player| &player.name == name);
+            let conn = &conn;
+            async move {
+                match known {
+                    Some(player) => player.identity.clone(),
+                    None => get_player_name(conn, name).await,
+                }
+            }
+        }))
+        .await;
+
+        // Store the snapshot for the UI to display
+        *self.status.lock().unwrap() = StatusSnapshot {
+            now_playing,
+            playing_players,
+            blocking_players,
+            active_inhibitors,
+        };
+    }
+
+    pub async fn update_state(&self) -> anyhow::Result<()> {
+        self.update_state_for(None).await
+    }
+
+    pub fn get_tracked_player_count(&self) -> usize {
+        self.tracked_players.load(Ordering::SeqCst)
+    }
+
+    pub async fn update_state_for(&self, media_players: Option<Vec<String>>) -> anyhow::Result<()> {
+        // Count the players whenever they have been listed (e.g. a player was added or removed)
+        if let Some(media_players) = &media_players {
+            self.tracked_players.store(media_players.len(), Ordering::SeqCst);
+        }
+
+        // Remember if the screen was being kept awake before the update
+        let was_blocked = self.screensaver.is_blocked();
+
+        // Block/unblock the screensaver to match the playing media, sharing the players it found with the UI
+        let playing_players = self.screensaver.update_state(&self.get_connection(), media_players).await?;
+        self.refresh_status(playing_players.unwrap_or_default()).await;
+
+        // Let the user know when the screen starts/stops being kept awake
+        let is_blocked = self.screensaver.is_blocked();
+        if is_blocked && !was_blocked {
+            let body = self
+                .get_status()
+                .now_playing
+                .map(|now_playing| now_playing.describe())
+                .unwrap_or_default();
+            self.notify(NotificationCategory::Block, "Screen kept awake", body);
+        } else if !is_blocked && was_blocked {
+            self.notify(NotificationCategory::Unblock, "Screen sleep re-enabled", String::new());
+        }
+
+        // Let the D-Bus clients know the state may have changed
+        if let Err(e) = emit_state_changed(&self.get_connection()).await {
+            log::debug!("[MANAGER] Failed to emit the changed properties: {}", e);
+        }
+
+        // Request the UI to refresh
+        self.ui_channel.request_refresh()?;
+
+        Ok(())
+    }
+
+    pub fn notify(&self, category: NotificationCategory, summary: &str, body: String) {
+        // Send the notification in the background, so a slow notification server cannot hold up updates
+        let notifier = self.notifier.clone();
+        let conn = self.get_connection();
+        let summary = summary.to_string();
+        async_std::task::spawn(async move {
+            // Block/unblock notifications are coalesced, so a burst of flips does not spam the user
+            let result = match category {
+                NotificationCategory::Block | NotificationCategory::Unblock => {
+                    notifier.notify_transition(&conn, category, &summary, &body).await
+                }
+                NotificationCategory::Error => notifier.notify(&conn, category, &summary, &body).await,
+            };
+            if let Err(e) = result {
+                log::warn!("[SYSTEM] Failed to send notification: {}", e);
+            }
+        });
+    }
+
+    pub async fn release_orphaned_inhibitors(&self) -> anyhow::Result<()> {
+        // List our inhibitors and release them
+        let released = self
+            .screensaver
+            .release_orphaned_inhibitors(&self.get_connection(), self.system_connection.as_ref())
+            .await?;
+        log::info!("[SYSTEM] Released {} inhibitors held by MediaBlocker", released.len());
+
+        // Re-evaluate the players, so an inhibit is taken again if media is still playing
+        self.tray_channel.request_refresh()?;
+
+        Ok(())
+    }
+
+    pub async fn boss_key(&self) -> anyhow::Result<()> {
+        log::info!("[SYSTEM] Boss key activated. Clearing the screen...");
+
+        // Disable the blocker first, so the players pausing cannot trigger a new block
+        self.cancel_snooze();
+        self.screensaver.disallow_updates();
+
+        // Pause every media player on the D-Bus session
+        pause_all_players(&self.get_connection(), &self.get_player_filter()).await?;
+
+        // Release the inhibits, including the user's hold, so the screensaver can take over immediately
+        let conn = self.get_connection();
+        self.screensaver.set_manual_hold(&conn, false).await?;
+        self.screensaver.force_unblock(&conn).await?;
+        self.refresh_status(Vec::new()).await;
+
+        // Request the UI to refresh
+        self.ui_channel.request_refresh()?;
+
+        Ok(())
+    }
+
     pub async fn run(self: Arc<Self>) {
         log::info!("[SYSTEM] MediaBlocker starting...");
 
+        // Expose the state of the blocker over D-Bus, the blocker works without it
+        Self::serve_manager(self.clone()).await;
+
         // Monitor the additional/removal of media players from the D-Bus
         log::debug!(
             "[SYSTEM] Spawning Media Monitor to track the addition/removal of media players..."
         );
-        let media_app = self.clone();
-        async_std::task::spawn(async move {
-            if let Err(e) = MediaMonitor::start(&media_app).await {
-                log::error!("[DISCOVERY] Media Monitor has crashed: {}", e)
-            }
-        });
+        async_std::task::spawn(self.clone().supervise("[DISCOVERY]", "Media Monitor", |app| async move {
+            MediaMonitor::start(&app).await
+        }));
 
         // Monitor the playback status of active media players
         log::debug!(
             "[SYSTEM] Spawning Playback Monitor to track playback status of active media players..."
         );
-        let playback_app = self.clone();
-        async_std::task::spawn(async move {
-            if let Err(e) = PlaybackMonitor::start(&playback_app).await {
-                log::error!("[PLAYBACK] Playback Monitor has crashed: {}", e)
+        async_std::task::spawn(self.clone().supervise("[PLAYBACK]", "Playback Monitor", |app| async move {
+            PlaybackMonitor::start(&app).await
+        }));
+    }
+
+    async fn supervise<F, Fut>(self: Arc<Self>, section: &str, monitor: &str, start: F)
+    where
+        F: Fn(Arc<Application>) -> Fut,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        // The number of times the monitor has crashed in a row
+        let mut crashes: u32 = 0;
+
+        loop {
+            // Run the monitor until it exits, remembering the connection it was started with
+            let conn = self.get_connection();
+            let started = Instant::now();
+            let error = run_monitor_once(monitor, start(self.clone())).await;
+
+            // Report the crash
+            log::error!("{} {} has crashed: {}", section, monitor, error);
+            self.set_healthy(false).await;
+            self.notify(NotificationCategory::Error, &format!("{} has crashed", monitor), error.to_string());
+            self.exit_if_requested();
+
+            // A monitor that ran for a while before crashing is counted as a new run of crashes
+            if started.elapsed() > MONITOR_RESTART_MAX_BACKOFF {
+                crashes = 0;
+            }
+            crashes += 1;
+
+            // Wait before restarting, so a persistent failure does not spin
+            let backoff = restart_backoff(crashes);
+            log::info!(
+                "{} Restarting the {} in {}s (crash {} in a row)",
+                section,
+                monitor,
+                backoff.as_secs(),
+                crashes
+            );
+            async_std::task::sleep(backoff).await;
+
+            // Replace the connection if the crash was caused by the session bus dropping
+            match self.reconnect_if_dropped(&conn).await {
+                // Serve the D-Bus interface on the new connection
+                Ok(true) => Self::serve_manager(self.clone()).await,
+                Ok(false) => {}
+                Err(e) => {
+                    log::warn!("{} Unable to reconnect to the D-Bus session: {}", section, e);
+                    continue;
+                }
+            }
+            self.set_healthy(true).await;
+        }
+    }
+
+    async fn connect_system(dbus_timeout: Duration) -> zbus::Result<Connection> {
+        connection::Builder::system()?.method_timeout(dbus_timeout).build().await
+    }
+
+    async fn serve_manager(self: Arc<Self>) {
+        if let Err(e) = serve(self).await {
+            log::warn!("[MANAGER] Unable to serve the D-Bus interface: {}", e);
+        }
+    }
+
+    async fn reconnect_if_dropped(&self, failed: &Connection) -> anyhow::Result<bool> {
+        // The other monitor may have already replaced the connection
+        if self.get_connection().unique_name() != failed.unique_name() {
+            return Ok(false);
+        }
+
+        // A connection that still answers was not the cause of the crash
+        let alive = match DBusProxy::new(failed).await {
+            Ok(dbus) => dbus.get_id().await.is_ok(),
+            Err(_) => false,
+        };
+        if alive {
+            return Ok(false);
+        }
+
+        // Connect to the session bus again
+        log::warn!("[SYSTEM] The D-Bus session connection has dropped, reconnecting...");
+        let conn = connection::Builder::session()?.method_timeout(self.dbus_timeout).build().await?;
+
+        // Swap in the new connection, unless the other monitor reconnected while this one was connecting
+        {
+            let mut current = self.connection.write().unwrap();
+            if current.unique_name() != failed.unique_name() {
+                return Ok(false);
             }
+            *current = conn;
+        }
+
+        // The desktop released our inhibits when the old connection closed, and the proxies closed with it
+        self.screensaver.forget_inhibits();
+        clear_player_proxies();
+        log::info!("[SYSTEM] Reconnected to the D-Bus session");
+
+        Ok(true)
+    }
+
+    fn exit_if_requested(&self) {
+        // Without monitoring the app is useless, so let a supervisor restart it if asked to
+        if self.exit_on_failure {
+            log::error!("[SYSTEM] Exiting as MEDIABLOCKER_EXIT_ON_FAILURE is set");
+            ExitStatus::MonitorCrashed.exit();
+        }
+    }
+}
+
+async fn run_monitor_once(
+    monitor: &str,
+    run: impl Future<Output = anyhow::Result<()>> + Send + 'static,
+) -> anyhow::Error {
+    // Run the monitor in its own task, catching a panic so it is restarted like any other crash
+    let handle = async_std::task::spawn(AssertUnwindSafe(run).catch_unwind());
+
+    // Wait for the monitor to exit, which it only does when it has stopped working
+    match handle.await {
+        // The monitors only return once their signal stream has ended (e.g. the bus dropped)
+        Ok(Ok(())) => anyhow::anyhow!("{} stopped unexpectedly", monitor),
+        Ok(Err(e)) => e,
+        Err(_) => anyhow::anyhow!("{} panicked", monitor),
+    }
+}
+
+fn restart_backoff(crashes: u32) -> Duration {
+    // Double the delay for each crash in a row, up to the maximum
+    let doublings = crashes.saturating_sub(1).min(16);
+    (MONITOR_RESTART_MIN_BACKOFF * 2u32.pow(doublings)).min(MONITOR_RESTART_MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::media_player::playing_players;
+    use crate::app::screensaver::ScreensaverStatus;
+    use crate::test_support::{fake_app, FakeBackend, FakePlayback, FakePlayer, PrivateBus};
+    use async_std::task;
+
+    #[test]
+    #[ignore = "starts a private dbus-daemon"]
+    fn boss_key_pauses_the_players_and_releases_every_inhibit() {
+        task::block_on(async {
+            let bus = PrivateBus::start().unwrap();
+            let name = "org.mpris.MediaPlayer2.bossplayer";
+            let _player = FakePlayer::serve(&bus, name, "Boss Player", "Playing").await.unwrap();
+            let (backend, playback) = (FakeBackend::default(), FakePlayback::default());
+            playback.set_playing(&[name]);
+            let app = fake_app(bus.connect().await.unwrap(), Some(backend.clone()), &playback);
+
+            app.update_state().await.unwrap();
+            app.set_manual_hold(true).await.unwrap();
+            assert_eq!(backend.inhibit_count(), 2);
+
+            app.boss_key().await.unwrap();
+
+            let screensaver = app.get_screensaver();
+            assert!(!screensaver.is_blocked());
+            assert!(!screensaver.is_manually_held());
+            assert_eq!(screensaver.status(), ScreensaverStatus::Disabled);
+            assert_eq!(backend.uninhibit_count(), 2);
+            assert!(playing_players(&app.get_connection(), &PlayerFilter::default()).await.unwrap().is_empty());
         });
     }
 }
@@ -0,0 +1,30 @@
+use std::process::Command;
+
+pub async fn is_audio_active() -> anyhow::Result<bool> {
+    // pactl talks to PulseAudio (or PipeWire's Pulse server), run it off the async threads as it blocks
+    let output = async_std::task::spawn_blocking(|| {
+        Command::new("pactl")
+            .args(["list", "sink-inputs"])
+            .env("LC_ALL", "C")
+            .output()
+    })
+    .await?;
+    if !output.status.success() {
+        anyhow::bail!("pactl exited with {}", output.status);
+    }
+
+    Ok(has_active_stream(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn has_active_stream(sink_inputs: &str) -> bool {
+    // Each stream is a block starting with "Sink Input #N", a stream is outputting if it is neither corked nor muted
+    sink_inputs.split("Sink Input #").skip(1).any(|stream| {
+        let field = |name: &str| {
+            stream
+                .lines()
+                .find_map(|line| line.trim().strip_prefix(name))
+                .map(str::trim)
+        };
+        field("Corked:") == Some("no") && field("Mute:") == Some("no")
+    })
+}
@@ -0,0 +1,49 @@
+use crate::app::backends::{InhibitBackend, InhibitHandle};
+use crate::global_constants::{SCREENSAVER_DESTINATION, SCREENSAVER_INTERFACE, SCREENSAVER_PATH};
+use async_trait::async_trait;
+use zbus::{Connection, Proxy};
+
+// Inhibits through org.freedesktop.ScreenSaver, as exposed by KDE, Xfce and most X11 screensavers
+pub struct FreedesktopScreenSaver;
+
+#[async_trait]
+impl InhibitBackend for FreedesktopScreenSaver {
+    fn name(&self) -> &'static str {
+        "org.freedesktop.ScreenSaver"
+    }
+
+    async fn inhibit(&self, conn: &Connection, app_name: &str, reason: &str) -> anyhow::Result<InhibitHandle> {
+        // Open a new proxy to the screensaver
+        let screensaver = Proxy::new(
+            conn,
+            SCREENSAVER_DESTINATION,
+            SCREENSAVER_PATH,
+            SCREENSAVER_INTERFACE,
+        )
+        .await?;
+
+        // Call the inhibit method to block the screen
+        let cookie: u32 = screensaver.call("Inhibit", &(app_name, reason)).await?;
+        Ok(InhibitHandle::Cookie(cookie))
+    }
+
+    async fn uninhibit(&self, conn: &Connection, handle: InhibitHandle) -> anyhow::Result<()> {
+        // This backend only hands out cookies
+        let InhibitHandle::Cookie(cookie) = handle else {
+            anyhow::bail!("{} cannot release an inhibit it did not take", self.name());
+        };
+
+        // Open a new proxy to the screensaver
+        let screensaver = Proxy::new(
+            conn,
+            SCREENSAVER_DESTINATION,
+            SCREENSAVER_PATH,
+            SCREENSAVER_INTERFACE,
+        )
+        .await?;
+
+        // Remove the inhibit cookie and unblock the screen
+        screensaver.call::<_, _, ()>("UnInhibit", &(cookie)).await?;
+        Ok(())
+    }
+}
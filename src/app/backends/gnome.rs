@@ -0,0 +1,52 @@
+use crate::app::backends::{InhibitBackend, InhibitHandle};
+use crate::global_constants::{GNOME_INHIBIT_IDLE, GNOME_SESSION_MANAGER_DESTINATION, GNOME_SESSION_MANAGER_INTERFACE, GNOME_SESSION_MANAGER_PATH};
+use async_trait::async_trait;
+use zbus::{Connection, Proxy};
+
+// Inhibits through org.gnome.SessionManager, as GNOME does not register org.freedesktop.ScreenSaver
+pub struct GnomeSessionManager;
+
+#[async_trait]
+impl InhibitBackend for GnomeSessionManager {
+    fn name(&self) -> &'static str {
+        "org.gnome.SessionManager"
+    }
+
+    async fn inhibit(&self, conn: &Connection, app_name: &str, reason: &str) -> anyhow::Result<InhibitHandle> {
+        // Open a new proxy to the session manager
+        let session_manager = Proxy::new(
+            conn,
+            GNOME_SESSION_MANAGER_DESTINATION,
+            GNOME_SESSION_MANAGER_PATH,
+            GNOME_SESSION_MANAGER_INTERFACE,
+        )
+        .await?;
+
+        // Inhibit the session from going idle, there is no toplevel window so its XID is 0
+        let toplevel_xid: u32 = 0;
+        let cookie: u32 = session_manager
+            .call("Inhibit", &(app_name, toplevel_xid, reason, GNOME_INHIBIT_IDLE))
+            .await?;
+        Ok(InhibitHandle::Cookie(cookie))
+    }
+
+    async fn uninhibit(&self, conn: &Connection, handle: InhibitHandle) -> anyhow::Result<()> {
+        // This backend only hands out cookies
+        let InhibitHandle::Cookie(cookie) = handle else {
+            anyhow::bail!("{} cannot release an inhibit it did not take", self.name());
+        };
+
+        // Open a new proxy to the session manager
+        let session_manager = Proxy::new(
+            conn,
+            GNOME_SESSION_MANAGER_DESTINATION,
+            GNOME_SESSION_MANAGER_PATH,
+            GNOME_SESSION_MANAGER_INTERFACE,
+        )
+        .await?;
+
+        // Remove the inhibit cookie and unblock the screen
+        session_manager.call::<_, _, ()>("Uninhibit", &(cookie)).await?;
+        Ok(())
+    }
+}
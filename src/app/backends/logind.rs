@@ -0,0 +1,44 @@
+use crate::app::backends::{InhibitBackend, InhibitHandle};
+use crate::global_constants::{LOGIN1_DESTINATION, LOGIN1_INTERFACE, LOGIN1_PATH};
+use async_trait::async_trait;
+use zbus::{Connection, Proxy};
+use zvariant::OwnedFd;
+
+// Inhibits through systemd-logind, for desktops without a ScreenSaver service on the D-Bus session
+pub struct LogindInhibitor {
+    /// Connection to the D-Bus system bus, where logind is registered
+    system_conn: Connection,
+}
+
+impl LogindInhibitor {
+    pub fn new(system_conn: Connection) -> Self {
+        Self { system_conn }
+    }
+}
+
+#[async_trait]
+impl InhibitBackend for LogindInhibitor {
+    fn name(&self) -> &'static str {
+        "org.freedesktop.login1"
+    }
+
+    async fn inhibit(&self, _conn: &Connection, app_name: &str, reason: &str) -> anyhow::Result<InhibitHandle> {
+        // Open a new proxy to the logind manager on the system bus
+        let manager = Proxy::new(&self.system_conn, LOGIN1_DESTINATION, LOGIN1_PATH, LOGIN1_INTERFACE).await?;
+
+        // Take a blocking idle inhibitor, which is held for as long as the returned fd is open
+        let fd: OwnedFd = manager.call("Inhibit", &("idle", app_name, reason, "block")).await?;
+        Ok(InhibitHandle::Fd(fd))
+    }
+
+    async fn uninhibit(&self, _conn: &Connection, handle: InhibitHandle) -> anyhow::Result<()> {
+        // This backend only hands out file descriptors
+        let InhibitHandle::Fd(fd) = handle else {
+            anyhow::bail!("{} cannot release an inhibit it did not take", self.name());
+        };
+
+        // logind releases the inhibitor once every copy of the fd is closed, which dropping does
+        drop(fd);
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+mod freedesktop;
+mod gnome;
+mod logind;
+mod simulate;
+
+pub use freedesktop::FreedesktopScreenSaver;
+pub use gnome::GnomeSessionManager;
+pub use logind::LogindInhibitor;
+pub use simulate::SimulateActivity;
+
+use crate::global_constants::{DBUS_DESTINATION, DBUS_INTERFACE, DBUS_PATH, GNOME_SESSION_MANAGER_DESTINATION, LOGIN1_DESTINATION, SCREENSAVER_DESTINATION};
+use async_trait::async_trait;
+use zbus::{Connection, Proxy};
+use zvariant::OwnedFd;
+
+// Identifies an inhibit taken by a backend, so the same inhibit can be released later
+#[derive(Debug)]
+pub enum InhibitHandle {
+    /// A cookie returned by the ScreenSaver and SessionManager services
+    Cookie(u32),
+
+    /// A file descriptor returned by logind, the inhibit is held until it is closed
+    Fd(OwnedFd),
+
+    /// The ID of a loop that keeps reporting user activity, the screen is kept awake until it is stopped
+    Loop(u64),
+}
+
+// A desktop service that can keep the screen awake on behalf of the application
+#[async_trait]
+pub trait InhibitBackend: Send + Sync {
+    /// The name of the backend, for logging which one is in use
+    fn name(&self) -> &'static str;
+
+    /// Take an inhibit, returning the handle that identifies it
+    async fn inhibit(&self, conn: &Connection, app_name: &str, reason: &str) -> anyhow::Result<InhibitHandle>;
+
+    /// Release the inhibit identified by the handle
+    async fn uninhibit(&self, conn: &Connection, handle: InhibitHandle) -> anyhow::Result<()>;
+}
+
+// The kinds of inhibit backend, in the order they are preferred when detecting one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackendKind {
+    Freedesktop,
+    Gnome,
+    Logind,
+}
+
+impl BackendKind {
+    pub const ALL: [BackendKind; 3] = [BackendKind::Freedesktop, BackendKind::Gnome, BackendKind::Logind];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        // Match the names accepted by MEDIABLOCKER_BACKEND
+        match name.trim().to_lowercase().as_str() {
+            "freedesktop" => Some(BackendKind::Freedesktop),
+            "gnome" => Some(BackendKind::Gnome),
+            "logind" => Some(BackendKind::Logind),
+            _ => None,
+        }
+    }
+
+    pub fn bus_name(&self) -> &'static str {
+        match self {
+            BackendKind::Freedesktop => SCREENSAVER_DESTINATION,
+            BackendKind::Gnome => GNOME_SESSION_MANAGER_DESTINATION,
+            BackendKind::Logind => LOGIN1_DESTINATION,
+        }
+    }
+
+    pub fn is_on_system_bus(&self) -> bool {
+        // logind is registered on the system bus, the other services on the D-Bus session
+        matches!(self, BackendKind::Logind)
+    }
+}
+
+pub fn choose_backend(session_names: &[String], system_names: &[String]) -> Option<BackendKind> {
+    // Pick the first backend in order of preference whose service is registered on its bus
+    BackendKind::ALL.into_iter().find(|kind| {
+        let names = if kind.is_on_system_bus() { system_names } else { session_names };
+        names.iter().any(|name| name == kind.bus_name())
+    })
+}
+
+pub async fn select_backend(
+    conn: &Connection,
+    system_conn: Option<&Connection>,
+    keep_awake_mode: &str,
+) -> Option<Box<dyn InhibitBackend>> {
+    // Report user activity on a timer rather than inhibiting, for desktops that ignore the inhibit
+    match keep_awake_mode.trim().to_lowercase().as_str() {
+        "simulate" => return Some(Box::new(SimulateActivity::new())),
+        "inhibit" | "" => {}
+        mode => log::warn!("[SCREENSAVER] Unknown keep awake mode: {}, inhibiting instead", mode),
+    }
+
+    // Use the backend named by MEDIABLOCKER_BACKEND when set, otherwise the preferred of the registered services
+    let requested = std::env::var("MEDIABLOCKER_BACKEND").ok();
+    let (session_names, system_names) = list_service_names(conn, system_conn).await;
+    let kind = resolve_backend(requested.as_deref(), &session_names, &system_names);
+    log::debug!("[SCREENSAVER] Selected inhibit backend: {:?}", kind);
+
+    // Construct the backend, logind needs the system bus to be available
+    match kind? {
+        BackendKind::Freedesktop => Some(Box::new(FreedesktopScreenSaver)),
+        BackendKind::Gnome => Some(Box::new(GnomeSessionManager)),
+        BackendKind::Logind => match system_conn {
+            Some(system_conn) => Some(Box::new(LogindInhibitor::new(system_conn.clone()))),
+            None => {
+                log::warn!("[SCREENSAVER] The logind backend needs the system bus, which is unavailable");
+                None
+            }
+        },
+    }
+}
+
+pub fn resolve_backend(
+    requested: Option<&str>,
+    session_names: &[String],
+    system_names: &[String],
+) -> Option<BackendKind> {
+    // A backend requested by name is used without checking for its service, an unknown name is ignored
+    if let Some(name) = requested.filter(|name| !name.trim().is_empty()) {
+        match BackendKind::from_name(name) {
+            Some(kind) => return Some(kind),
+            None => log::warn!("[SCREENSAVER] Unknown inhibit backend: {}, detecting one instead", name),
+        }
+    }
+
+    // Pick the preferred backend out of the registered services
+    choose_backend(session_names, system_names)
+}
+
+async fn list_service_names(conn: &Connection, system_conn: Option<&Connection>) -> (Vec<String>, Vec<String>) {
+    // List the services registered on each bus, a bus that cannot be listed has no usable services
+    let session_names = list_bus_names(conn).await.unwrap_or_else(|e| {
+        log::warn!("[SCREENSAVER] Failed to list the names on the D-Bus session: {}", e);
+        Vec::new()
+    });
+    let system_names = match system_conn {
+        Some(system_conn) => list_bus_names(system_conn).await.unwrap_or_else(|e| {
+            log::warn!("[SCREENSAVER] Failed to list the names on the D-Bus system bus: {}", e);
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+    (session_names, system_names)
+}
+
+pub async fn list_bus_names(conn: &Connection) -> anyhow::Result<Vec<String>> {
+    // Wrap the D-Bus daemon in a proxy layer to interface with methods or properties
+    let dbus = Proxy::new(conn, DBUS_DESTINATION, DBUS_PATH, DBUS_INTERFACE).await?;
+
+    // Get the names in the D-Bus
+    Ok(dbus.call("ListNames", &()).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn prefers_the_freedesktop_service() {
+        let session = names(&[SCREENSAVER_DESTINATION, GNOME_SESSION_MANAGER_DESTINATION]);
+        let system = names(&[LOGIN1_DESTINATION]);
+        assert_eq!(resolve_backend(None, &session, &system), Some(BackendKind::Freedesktop));
+    }
+
+    #[test]
+    fn falls_back_in_order_of_preference() {
+        let session = names(&[GNOME_SESSION_MANAGER_DESTINATION]);
+        let system = names(&[LOGIN1_DESTINATION]);
+        assert_eq!(resolve_backend(None, &session, &system), Some(BackendKind::Gnome));
+        assert_eq!(resolve_backend(None, &[], &system), Some(BackendKind::Logind));
+        assert_eq!(resolve_backend(None, &[], &[]), None);
+    }
+
+    #[test]
+    fn looks_for_each_service_on_its_own_bus() {
+        // logind is only registered on the system bus, the other services only on the D-Bus session
+        let session = names(&[LOGIN1_DESTINATION]);
+        let system = names(&[SCREENSAVER_DESTINATION, GNOME_SESSION_MANAGER_DESTINATION]);
+        assert_eq!(resolve_backend(None, &session, &system), None);
+    }
+
+    #[test]
+    fn requested_backend_overrides_the_detection() {
+        let session = names(&[SCREENSAVER_DESTINATION]);
+        assert_eq!(resolve_backend(Some("gnome"), &session, &[]), Some(BackendKind::Gnome));
+        assert_eq!(resolve_backend(Some(" Logind "), &session, &[]), Some(BackendKind::Logind));
+    }
+
+    #[test]
+    fn unknown_or_blank_request_is_detected_instead() {
+        let session = names(&[SCREENSAVER_DESTINATION]);
+        assert_eq!(resolve_backend(Some("kde"), &session, &[]), Some(BackendKind::Freedesktop));
+        assert_eq!(resolve_backend(Some(" "), &session, &[]), Some(BackendKind::Freedesktop));
+    }
+}
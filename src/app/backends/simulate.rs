@@ -0,0 +1,78 @@
+use crate::app::backends::{InhibitBackend, InhibitHandle};
+use crate::global_constants::{SCREENSAVER_DESTINATION, SCREENSAVER_INTERFACE, SCREENSAVER_PATH, SIMULATE_ACTIVITY_INTERVAL};
+use async_std::task::JoinHandle;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use zbus::{Connection, Proxy};
+
+// Keeps the screen awake by reporting user activity to org.freedesktop.ScreenSaver on a timer, for desktops
+// that ignore Inhibit when turning the display off
+#[derive(Default)]
+pub struct SimulateActivity {
+    /// The ID handed out for the next keep-awake loop
+    next_id: AtomicU64,
+
+    /// The keep-awake loops that are running, keyed by the ID handed out for each
+    loops: Mutex<HashMap<u64, JoinHandle<()>>>,
+}
+
+impl SimulateActivity {
+    pub fn new() -> Self {
+        Self {
+            next_id: AtomicU64::new(0),
+            loops: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl InhibitBackend for SimulateActivity {
+    fn name(&self) -> &'static str {
+        "org.freedesktop.ScreenSaver (SimulateUserActivity)"
+    }
+
+    async fn inhibit(&self, conn: &Connection, _app_name: &str, _reason: &str) -> anyhow::Result<InhibitHandle> {
+        // Open a new proxy to the screensaver
+        let screensaver = Proxy::new(
+            conn,
+            SCREENSAVER_DESTINATION,
+            SCREENSAVER_PATH,
+            SCREENSAVER_INTERFACE,
+        )
+        .await?;
+
+        // Report activity straight away, so a failure is reported to the caller like a failed Inhibit
+        screensaver.call::<_, _, ()>("SimulateUserActivity", &()).await?;
+
+        // Keep reporting activity until the loop is cancelled, well within the screensaver's idle timeout
+        let keep_awake = async_std::task::spawn(async move {
+            loop {
+                async_std::task::sleep(SIMULATE_ACTIVITY_INTERVAL).await;
+                if let Err(e) = screensaver.call::<_, _, ()>("SimulateUserActivity", &()).await {
+                    log::warn!("[SCREENSAVER] Failed to simulate user activity: {}", e);
+                }
+            }
+        });
+
+        // Hand out the ID of the loop, which the caller releases like a cookie
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.loops.lock().unwrap().insert(id, keep_awake);
+        Ok(InhibitHandle::Loop(id))
+    }
+
+    async fn uninhibit(&self, _conn: &Connection, handle: InhibitHandle) -> anyhow::Result<()> {
+        // This backend only hands out loop IDs
+        let InhibitHandle::Loop(id) = handle else {
+            anyhow::bail!("{} cannot release an inhibit it did not take", self.name());
+        };
+
+        // Stop the loop, letting the screensaver count down from the last reported activity
+        let keep_awake = self.loops.lock().unwrap().remove(&id);
+        if let Some(keep_awake) = keep_awake {
+            keep_awake.cancel().await;
+        }
+        Ok(())
+    }
+}
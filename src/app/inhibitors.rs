@@ -0,0 +1,79 @@
+use crate::global_constants::{KDE_POWER_MANAGEMENT_DESTINATION, KDE_POWER_MANAGEMENT_INTERFACE, KDE_POWER_MANAGEMENT_PATH, LOGIN1_DESTINATION, LOGIN1_INTERFACE, LOGIN1_PATH};
+use zbus::{Connection, Proxy};
+
+// An idle inhibitor that is active on the system
+#[derive(Clone, Debug)]
+pub struct Inhibitor {
+    /// The name of the application holding the inhibitor
+    pub app_name: String,
+
+    /// The reason the application gave for holding the inhibitor
+    pub reason: String,
+}
+
+pub async fn count_active_inhibitors(
+    conn: &Connection,
+    system_conn: Option<&Connection>,
+    use_kde: bool,
+) -> Option<usize> {
+    // A backend that cannot enumerate its inhibitors is not an error, the count is just unknown
+    match list_inhibitors(conn, system_conn, use_kde).await {
+        Ok(inhibitors) => Some(inhibitors.len()),
+        Err(e) => {
+            log::debug!("[SCREENSAVER] Unable to count the active inhibitors: {}", e);
+            None
+        }
+    }
+}
+
+pub async fn list_inhibitors(
+    conn: &Connection,
+    system_conn: Option<&Connection>,
+    use_kde: bool,
+) -> anyhow::Result<Vec<Inhibitor>> {
+    // KDE tracks its inhibitions itself, while other desktops go through logind on the system bus
+    if use_kde {
+        list_kde_inhibitions(conn).await
+    } else if let Some(system_conn) = system_conn {
+        list_logind_idle_inhibitors(system_conn).await
+    } else {
+        Err(anyhow::anyhow!("The system bus is not available"))
+    }
+}
+
+async fn list_kde_inhibitions(conn: &Connection) -> anyhow::Result<Vec<Inhibitor>> {
+    // Open a new proxy to KDE's PowerManagement policy agent
+    let power_management = Proxy::new(
+        conn,
+        KDE_POWER_MANAGEMENT_DESTINATION,
+        KDE_POWER_MANAGEMENT_PATH,
+        KDE_POWER_MANAGEMENT_INTERFACE,
+    )
+    .await?;
+
+    // List the (app name, reason) of every active inhibition
+    let inhibitions: Vec<(String, String)> = power_management.call("ListInhibitions", &()).await?;
+    Ok(inhibitions
+        .into_iter()
+        .map(|(app_name, reason)| Inhibitor { app_name, reason })
+        .collect())
+}
+
+async fn list_logind_idle_inhibitors(system_conn: &Connection) -> anyhow::Result<Vec<Inhibitor>> {
+    // Open a new proxy to the logind manager
+    let login1 = Proxy::new(system_conn, LOGIN1_DESTINATION, LOGIN1_PATH, LOGIN1_INTERFACE).await?;
+
+    // List the (what, who, why, mode, uid, pid) of every active inhibitor
+    let inhibitors: Vec<(String, String, String, String, u32, u32)> =
+        login1.call("ListInhibitors", &()).await?;
+
+    // Only keep the inhibitors that stop the session from going idle
+    Ok(inhibitors
+        .into_iter()
+        .filter(|(what, ..)| what.split(':').any(|lock| lock == "idle"))
+        .map(|(_, who, why, ..)| Inhibitor {
+            app_name: who,
+            reason: why,
+        })
+        .collect())
+}
@@ -0,0 +1,76 @@
+use crate::app::application::Application;
+use crate::global_constants::{MANAGER_NAME, MANAGER_PATH};
+use std::sync::Arc;
+use zbus::{interface, Connection};
+
+// MediaBlocker's own D-Bus interface, so a keyboard shortcut or panel widget can control the blocker
+pub struct ManagerInterface {
+    /// The application state being exposed
+    app: Arc<Application>,
+}
+
+#[interface(name = "org.mediablocker.Manager")]
+impl ManagerInterface {
+    #[zbus(property)]
+    async fn enabled(&self) -> bool {
+        self.app.get_screensaver().status().is_enabled()
+    }
+
+    #[zbus(property)]
+    async fn blocking(&self) -> bool {
+        self.app.get_screensaver().status().is_blocking()
+    }
+
+    #[zbus(property)]
+    async fn active_players(&self) -> Vec<String> {
+        self.app.get_status().blocking_players
+    }
+
+    async fn enable(&self) -> zbus::fdo::Result<()> {
+        self.set_enabled(true).await
+    }
+
+    async fn disable(&self) -> zbus::fdo::Result<()> {
+        self.set_enabled(false).await
+    }
+
+    async fn toggle(&self) -> zbus::fdo::Result<()> {
+        self.set_enabled(!self.app.get_screensaver().status().is_enabled()).await
+    }
+}
+
+impl ManagerInterface {
+    async fn set_enabled(&self, enabled: bool) -> zbus::fdo::Result<()> {
+        log::info!(
+            "[MANAGER] {} requested over D-Bus",
+            if enabled { "Enable" } else { "Disable" }
+        );
+        self.app
+            .set_enabled(enabled)
+            .await
+            .map_err(|e| zbus::fdo::Error::Failed(e.to_string()))
+    }
+}
+
+pub async fn serve(app: Arc<Application>) -> anyhow::Result<()> {
+    // Register the interface on the current connection, then claim the well-known name
+    let conn = app.get_connection();
+    conn.object_server().at(MANAGER_PATH, ManagerInterface { app }).await?;
+    conn.request_name(MANAGER_NAME).await?;
+    log::info!("[MANAGER] Serving {} at {}", MANAGER_NAME, MANAGER_PATH);
+    Ok(())
+}
+
+pub async fn emit_state_changed(conn: &Connection) -> zbus::Result<()> {
+    // Let the clients watching the properties know they have changed
+    let interface = conn
+        .object_server()
+        .interface::<_, ManagerInterface>(MANAGER_PATH)
+        .await?;
+    let manager = interface.get().await;
+    let emitter = interface.signal_emitter();
+    manager.enabled_changed(emitter).await?;
+    manager.blocking_changed(emitter).await?;
+    manager.active_players_changed(emitter).await?;
+    Ok(())
+}
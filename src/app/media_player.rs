@@ -1,92 +1,898 @@
-use crate::global_constants::{DbusSignalStream, DBUS_DESTINATION, DBUS_INTERFACE, DBUS_PATH, MEDIA_PLAYER_INTERFACE, MEDIA_PLAYER_PATH};
-use crate::utils::{is_media_player, is_playback_running};
-use zbus::{Connection, Proxy};
-use zvariant::OwnedValue;
+use crate::app::audio::is_audio_active;
+use crate::global_constants::{DbusSignalStream, DBUS_DESTINATION, DBUS_INTERFACE, DBUS_PATH, MEDIA_PLAYER_CONTROL_INTERFACE, MEDIA_PLAYER_INTERFACE, MEDIA_PLAYER_PATH, MEDIA_PLAYER_ROOT_INTERFACE};
+use crate::config::Config;
+use async_trait::async_trait;
+use crate::utils::{is_audio_only_url, is_media_player, parse_playback_status, player_base_name, player_display_name, player_matches, should_track_player};
+use futures::future::join_all;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use zbus::message::Type;
+use zbus::{Connection, MatchRule, Message, MessageStream, Proxy};
+use zvariant::{OwnedValue, Value};
 
-pub async fn get_media_player_streams(conn: &Connection) -> anyhow::Result<Vec<DbusSignalStream>> {
-    // Get a list of all the media players
-    let media_players = get_media_player_names(conn).await?;
+// The number of media players dropped by the cap when the media players were last listed
+static DROPPED_PLAYERS: AtomicUsize = AtomicUsize::new(0);
 
-    // Define a mutable list of streams for each of the players
-    let mut streams = Vec::new();
+// Proxies to the properties of the media players, reused across updates until the player is removed
+static PLAYER_PROXIES: LazyLock<Mutex<HashMap<String, Proxy<'static>>>> = LazyLock::new(Default::default);
+
+// The names the media players give themselves, which do not change while the player is running
+static PLAYER_NAMES: LazyLock<Mutex<HashMap<String, String>>> = LazyLock::new(Default::default);
+
+// The playback status of a media player, as defined by the MPRIS PlaybackStatus property
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    Stopped,
+
+    /// The player reported a value that is not part of the MPRIS specification
+    Unknown,
+}
+
+// A player filter that the user can change while the monitors are running
+pub type SharedPlayerFilter = Arc<RwLock<PlayerFilter>>;
+
+// Which media players are allowed to keep the screen awake
+#[derive(Clone, Debug, Default)]
+pub struct PlayerFilter {
+    /// Only these media players are tracked (every player if empty)
+    pub allowlist: Vec<String>,
+
+    /// These media players are not tracked (ignored if the allowlist is set)
+    pub denylist: Vec<String>,
+
+    /// Only players playing video can keep the screen awake
+    pub video_only: bool,
+
+    /// Paused players keep the screen awake as well as playing players
+    pub keep_awake_while_paused: bool,
+
+    /// Players only keep the screen awake while a sound is being output
+    pub require_active_audio: bool,
+
+    /// Players whose track's URL has one of these schemes are ignored (e.g. "file")
+    pub ignored_url_schemes: Vec<String>,
+
+    /// The most media players that are tracked, as a safety valve for abnormal buses (None if unbounded)
+    pub max_players: Option<usize>,
+}
+
+impl PlayerFilter {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            allowlist: config.allowlist.clone(),
+            denylist: config.denylist.clone(),
+            video_only: config.video_only,
+            keep_awake_while_paused: config.keep_awake_while_paused,
+            require_active_audio: config.require_active_audio,
+            ignored_url_schemes: config.ignored_url_schemes.clone(),
+            max_players: Some(config.max_players).filter(|max_players| *max_players > 0),
+        }
+    }
+
+    pub fn set_tracked(&mut self, player: &str, tracked: bool) {
+        // The lists hold the name of the player without its bus prefix or instance suffix
+        let entry = player_base_name(player).to_lowercase();
+        let is_entry = |existing: &String| existing.trim().eq_ignore_ascii_case(&entry);
+
+        if tracked {
+            // A player is tracked once it is off the denylist, and on the allowlist (if one is in use)
+            self.denylist.retain(|existing| !is_entry(existing));
+            if !self.allowlist.is_empty() && !self.allowlist.iter().any(is_entry) {
+                self.allowlist.push(entry);
+            }
+        } else if !self.allowlist.is_empty() {
+            // Take the player off the allowlist, an allowlist left empty would track every player
+            self.allowlist.retain(|existing| !is_entry(existing));
+            if self.allowlist.is_empty() {
+                self.denylist.push(entry);
+            }
+        } else if !self.denylist.iter().any(is_entry) {
+            self.denylist.push(entry);
+        }
+    }
+
+    pub fn needs_track_url(&self) -> bool {
+        // The URL of the track decides if it is video, and if its scheme is ignored
+        self.video_only || !self.ignored_url_schemes.is_empty()
+    }
+
+    pub fn ignores_url(&self, url: &str) -> bool {
+        // Match the scheme of the URL, accepting entries written as "file", "file:" or "file://"
+        let Some((scheme, _)) = url.split_once(':') else {
+            return false;
+        };
+        self.ignored_url_schemes.iter().any(|ignored| {
+            let ignored = ignored.trim().trim_end_matches('/').trim_end_matches(':');
+            scheme.eq_ignore_ascii_case(ignored)
+        })
+    }
+
+    pub fn cap_players(&self, mut media_players: Vec<String>) -> (Vec<String>, Vec<String>) {
+        // Without a cap (or below it) every media player is kept
+        let Some(max_players) = self.max_players.filter(|max_players| media_players.len() > *max_players) else {
+            return (media_players, Vec::new());
+        };
+
+        // Keep the players matching the earliest allowlist entries first, then sort by name so the same
+        // media players are kept each time
+        media_players.sort_by_cached_key(|player| {
+            let rank = self.allowlist.iter().position(|entry| player_matches(player, entry));
+            (rank.unwrap_or(usize::MAX), player.clone())
+        });
+        let dropped = media_players.split_off(max_players);
+        (media_players, dropped)
+    }
+
+    pub fn keeps_awake(&self, status: PlaybackStatus) -> bool {
+        // Stopped (and unknown) players never keep the screen awake
+        match status {
+            PlaybackStatus::Playing => true,
+            PlaybackStatus::Paused => self.keep_awake_while_paused,
+            PlaybackStatus::Stopped | PlaybackStatus::Unknown => false,
+        }
+    }
+}
+
+// The playback status of a media player, as queried from the D-Bus session
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlayerStatus {
+    /// The bus name of the player (e.g. "org.mpris.MediaPlayer2.spotify")
+    pub name: String,
+
+    /// The name the player gives itself (e.g. "VLC media player"), falling back to its bus name
+    pub identity: String,
+
+    /// The playback status of the player (Unknown if the player did not report one)
+    pub status: PlaybackStatus,
+}
+
+// A media player on the D-Bus session, as listed to the user
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlayerInfo {
+    /// The bus name of the player (e.g. "org.mpris.MediaPlayer2.spotify")
+    pub name: String,
+
+    /// The name of the player to display to the user, as the player names itself (e.g. "VLC media player")
+    pub display_name: String,
+
+    /// The playback status of the player
+    pub status: PlaybackStatus,
+
+    /// Indicate if the player is allowed to keep the screen awake
+    pub tracked: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TrackMetadata {
+    /// The title of the current track (xesam:title)
+    pub title: Option<String>,
+
+    /// The artists of the current track joined into one string (xesam:artist)
+    pub artist: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NowPlaying {
+    /// The name of the player to display to the user
+    pub player: String,
+
+    /// The metadata of the track the player is playing
+    pub track: TrackMetadata,
+}
+
+impl NowPlaying {
+    pub fn describe(&self) -> String {
+        // Fall back to only the player when the track has no usable metadata
+        match (&self.track.title, &self.track.artist) {
+            (Some(title), Some(artist)) => format!("{} — {} by {}", self.player, title, artist),
+            (Some(title), None) => format!("{} — {}", self.player, title),
+            _ => self.player.clone(),
+        }
+    }
+}
+
+// Where the screensaver learns which media players are keeping the screen awake
+#[async_trait]
+pub trait PlaybackSource: Send + Sync {
+    /// List the players keeping the screen awake, out of the given players (every tracked player if None)
+    async fn playing_players(
+        &self,
+        conn: &Connection,
+        media_players: Option<Vec<String>>,
+    ) -> anyhow::Result<Vec<PlayerStatus>>;
+
+    /// Ask a player for its playback status, bypassing any kept result (Unknown if it does not answer)
+    async fn player_status(&self, conn: &Connection, player: &str) -> PlaybackStatus;
+
+    /// Forget any result kept from a previous call, so the next call queries the players
+    fn invalidate(&self);
+}
+
+pub struct PlayingMediaCache {
+    /// Which media players are queried
+    filter: SharedPlayerFilter,
+
+    /// How long a computed result can be reused before the players are queried again
+    ttl: Duration,
+
+    /// The last computed result, and the instant it was computed at
+    last_result: Mutex<Option<(Instant, Vec<PlayerStatus>)>>,
+}
+
+impl PlayingMediaCache {
+    pub fn new(ttl: Duration, filter: SharedPlayerFilter) -> Self {
+        Self {
+            filter,
+            ttl,
+            last_result: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl PlaybackSource for PlayingMediaCache {
+    async fn playing_players(
+        &self,
+        conn: &Connection,
+        media_players: Option<Vec<String>>,
+    ) -> anyhow::Result<Vec<PlayerStatus>> {
+        // Reuse the last result if it was computed within the TTL (and no new list of players was given)
+        if media_players.is_none()
+            && let Some((computed_at, playing)) = &*self.last_result.lock().unwrap()
+            && computed_at.elapsed() < self.ttl
+        {
+            log::trace!("[PLAYBACK] Reusing cached playback result: {:?}", playing);
+            return Ok(playing.clone());
+        }
+
+        // Otherwise, query the players for their playback status (listing the players if not given)
+        let filter = self.filter.read().unwrap().clone();
+        let media_players = match media_players {
+            Some(media_players) => media_players,
+            None => get_media_player_names(conn, &filter).await?,
+        };
+        let playing = playing_players_among(conn, media_players, &filter).await;
+
+        // Store the result for subsequent calls within the TTL
+        *self.last_result.lock().unwrap() = Some((Instant::now(), playing.clone()));
+
+        Ok(playing)
+    }
+
+    async fn player_status(&self, conn: &Connection, player: &str) -> PlaybackStatus {
+        // A player that has left the bus (or cannot be reached) has no status
+        match get_playback_status(conn, player).await {
+            Ok(status) => status.unwrap_or(PlaybackStatus::Unknown),
+            Err(e) => {
+                log::debug!("[PLAYBACK] {} -> Failed to get the playback status: {}", player, e);
+                PlaybackStatus::Unknown
+            }
+        }
+    }
+
+    fn invalidate(&self) {
+        // Clear the last result so the next call queries the players
+        *self.last_result.lock().unwrap() = None;
+    }
+}
+
+pub async fn get_media_player_stream(conn: &Connection) -> anyhow::Result<DbusSignalStream> {
+    // Match playback property changes from every media player with one rule, rather than
+    // registering a separate rule (and stream) for each of the media players
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .interface(MEDIA_PLAYER_INTERFACE)?
+        .member("PropertiesChanged")?
+        .path(MEDIA_PLAYER_PATH)?
+        .arg(0, MEDIA_PLAYER_CONTROL_INTERFACE)?
+        .build();
+
+    // Listen for any signals matching the rule
+    let stream = MessageStream::for_match_rule(rule, conn, None).await?;
+
+    // Drop any messages that failed to be received
+    let stream = stream.filter_map(|message| async move { message.ok() });
+    Ok(Box::pin(stream) as DbusSignalStream)
+}
+
+pub fn affects_playback(signal: &Message, filter: &PlayerFilter) -> anyhow::Result<bool> {
+    // PropertiesChanged carries the interface, the changed properties, and the invalidated properties
+    let (_, changed, invalidated): (String, HashMap<String, OwnedValue>, Vec<String>) =
+        signal.body().deserialize()?;
+
+    // The playback status decides if the screen is kept awake, and the metadata's URL can rule the track out
+    let is_relevant = |property: &str| {
+        property == "PlaybackStatus" || (filter.needs_track_url() && property == "Metadata")
+    };
+
+    Ok(changed.keys().any(|property| is_relevant(property))
+        || invalidated.iter().any(|property| is_relevant(property)))
+}
+
+pub async fn get_media_player_owners(
+    conn: &Connection,
+    media_players: &[String],
+) -> anyhow::Result<HashMap<String, String>> {
+    // Wrap the D-Bus daemon in a proxy layer to interface with methods or properties
+    let dbus = Proxy::new(conn, DBUS_DESTINATION, DBUS_PATH, DBUS_INTERFACE).await?;
+
+    // Define a mutable map of unique connection names to media player names
+    let mut owners = HashMap::new();
 
     // Loop over all the media player's
     for player_name in media_players {
-        // Create a proxy object for the playback properties of the media player
-        let player = Proxy::new(
-            conn,
-            player_name.clone(),
-            MEDIA_PLAYER_PATH,
-            MEDIA_PLAYER_INTERFACE,
-        )
-        .await?;
+        // Signals are sent from the unique name that owns the media player's name, so look it up
+        match dbus.call::<_, _, String>("GetNameOwner", &(&player_name)).await {
+            Ok(owner) => {
+                owners.insert(owner, player_name.clone());
+            }
+            Err(e) => {
+                log::warn!("[DISCOVERY] {} -> Failed to find the owner of the player: {}", player_name, e);
+            }
+        }
+    }
+
+    Ok(owners)
+}
+
+pub async fn playing_players(conn: &Connection, filter: &PlayerFilter) -> anyhow::Result<Vec<PlayerStatus>> {
+    // Get the names of the media players for the D-Bus session
+    let media_players = get_media_player_names(conn, filter).await?;
+    Ok(playing_players_among(conn, media_players, filter).await)
+}
+
+async fn playing_players_among(
+    conn: &Connection,
+    media_players: Vec<String>,
+    filter: &PlayerFilter,
+) -> Vec<PlayerStatus> {
+    // Query the players concurrently, so each player's round-trip does not add to the update's latency
+    let players = join_all(media_players.iter().map(|player_name| query_player(conn, player_name))).await;
+    let keeping_awake = join_all(players.iter().map(|player| is_keeping_awake(conn, player, filter))).await;
+
+    // Keep each of the media players that is keeping the screen awake
+    let playing: Vec<PlayerStatus> = players
+        .into_iter()
+        .zip(keeping_awake)
+        .filter_map(|(player, is_keeping_awake)| is_keeping_awake.then_some(player))
+        .collect();
+
+    // Veto the players if nothing is being heard (e.g. a stuck "Playing" status, or muted output)
+    if filter.require_active_audio && !playing.is_empty() {
+        match is_audio_active().await {
+            Ok(true) => {}
+            Ok(false) => {
+                let names: Vec<&str> = playing.iter().map(|player| player.name.as_str()).collect();
+                log::trace!("[PLAYBACK] No sound is being output, ignoring: {}", names.join(", "));
+                return Vec::new();
+            }
+            // The audio cannot be checked (e.g. pactl is not installed), so trust the players
+            Err(e) => log::debug!("[PLAYBACK] Failed to check for sound being output: {}", e),
+        }
+    }
+
+    playing
+}
 
-        // Listen for any changes in the properties of the media player
-        if let Ok(stream) = player.receive_signal("PropertiesChanged").await {
-            streams.push(Box::pin(stream) as DbusSignalStream);
-        } else {
-            eprintln!("Failed to register signal for player: {}", player_name);
+pub async fn first_playing_player(
+    conn: &Connection,
+    filter: &PlayerFilter,
+) -> anyhow::Result<Option<PlayerStatus>> {
+    // Take the first of the players keeping the screen awake (None if none are)
+    Ok(playing_players(conn, filter).await?.into_iter().next())
+}
+
+pub async fn get_now_playing(
+    conn: &Connection,
+    filter: &PlayerFilter,
+) -> anyhow::Result<Option<NowPlaying>> {
+    // Find the player that is keeping the screen awake
+    let Some(player) = first_playing_player(conn, filter).await? else {
+        return Ok(None);
+    };
+
+    Ok(Some(describe_now_playing(conn, &player).await))
+}
+
+pub async fn describe_now_playing(conn: &Connection, player: &PlayerStatus) -> NowPlaying {
+    // Read the metadata of the track, treating unreadable metadata as missing
+    let track = match get_track_metadata(conn, &player.name).await {
+        Ok(track) => track,
+        Err(e) => {
+            log::debug!("[PLAYBACK] {} -> Failed to read track metadata: {}", player.name, e);
+            TrackMetadata::default()
         }
+    };
+
+    NowPlaying {
+        player: player.identity.clone(),
+        track,
     }
+}
+
+pub async fn get_track_metadata(conn: &Connection, player: &str) -> anyhow::Result<TrackMetadata> {
+    // Get the metadata map of the current track from the player
+    let metadata = get_metadata(conn, player).await?;
+
+    // Extract the title, ignoring blank titles some players report between tracks
+    let title = metadata
+        .get("xesam:title")
+        .and_then(|value| value.downcast_ref::<&str>().ok())
+        .map(|title| title.trim().to_string())
+        .filter(|title| !title.is_empty());
+
+    // Extract the artists, which MPRIS defines as a list of names
+    let artist = metadata
+        .get("xesam:artist")
+        .and_then(|value| Value::try_clone(value).ok())
+        .and_then(|value| value.downcast::<Vec<String>>().ok())
+        .map(|artists| artists.join(", "))
+        .filter(|artist| !artist.is_empty());
 
-    Ok(streams)
+    Ok(TrackMetadata { title, artist })
 }
 
-pub async fn any_playing_media(conn: &Connection) -> anyhow::Result<bool> {
+pub async fn list_players(conn: &Connection, filter: &PlayerFilter) -> anyhow::Result<Vec<PlayerInfo>> {
+    // Get the names of every media player, including those the user does not want tracked
+    let media_players = list_media_player_names(conn).await?;
+    Ok(list_players_among(conn, media_players, filter, &[]).await)
+}
+
+pub async fn list_players_among(
+    conn: &Connection,
+    mut media_players: Vec<String>,
+    filter: &PlayerFilter,
+    known: &[PlayerStatus],
+) -> Vec<PlayerInfo> {
+    media_players.sort();
+
+    // Reuse the statuses that are already known, and query the other players concurrently
+    let players = join_all(media_players.iter().map(|player_name| async move {
+        match known.iter().find(|player| &player.name == player_name) {
+            Some(player) => player.clone(),
+            None => query_player(conn, player_name).await,
+        }
+    }))
+    .await;
+    players
+        .into_iter()
+        .map(|player| PlayerInfo {
+            tracked: should_track_player(&player.name, filter),
+            display_name: player.identity,
+            status: player.status,
+            name: player.name,
+        })
+        .collect()
+}
+
+async fn query_player(conn: &Connection, player_name: &str) -> PlayerStatus {
+    // Ask for the playback status and the name of the player at the same time
+    let (status, identity) = futures::join!(get_playback_status(conn, player_name), get_player_name(conn, player_name));
+
+    // A player that does not report a status is treated as unknown, which never keeps the screen awake
+    let status = match status {
+        Ok(status) => status.unwrap_or(PlaybackStatus::Unknown),
+        Err(e) => {
+            log::warn!("[PLAYBACK] {} -> Failed to get the playback status: {}", player_name, e);
+            PlaybackStatus::Unknown
+        }
+    };
+
+    PlayerStatus {
+        name: player_name.to_string(),
+        identity,
+        status,
+    }
+}
+
+pub async fn pause_all_players(conn: &Connection, filter: &PlayerFilter) -> anyhow::Result<()> {
     // Get the names of the media players for the D-Bus session
-    let media_players = get_media_player_names(&conn).await?;
+    let media_players = get_media_player_names(conn, filter).await?;
 
     // For each of the media players
     for player_name in media_players {
-        // Get and match on the playback status of the player
-        match get_playback_status(&conn, &player_name).await {
-            Ok(Some(status)) => {
-                // Check if the playback status indicates media is being played
-                if is_playback_running(&status) {
-                    return Ok(true);
-                }
-            }
-            Ok(None) => {}
+        // Open a proxy layer to the playback controls of the player
+        let controls = match Proxy::new(
+            conn,
+            player_name.clone(),
+            MEDIA_PLAYER_PATH,
+            MEDIA_PLAYER_CONTROL_INTERFACE,
+        )
+        .await
+        {
+            Ok(proxy) => proxy,
             Err(e) => {
-                eprintln!("{} -> Error getting playback status: {}", player_name, e);
+                log::warn!("[PLAYBACK] {} -> Failed to open playback controls: {}", player_name, e);
+                continue;
             }
+        };
+
+        // Ask the player to pause, a player that refuses should not stop the others from pausing
+        match controls.call::<_, _, ()>("Pause", &()).await {
+            Ok(_) => log::debug!("[PLAYBACK] {} has been paused", player_name),
+            Err(e) => log::warn!("[PLAYBACK] {} -> Failed to pause: {}", player_name, e),
         }
     }
 
-    // If no match was found no player is running
-    Ok(false)
+    Ok(())
+}
+
+async fn is_keeping_awake(conn: &Connection, player: &PlayerStatus, filter: &PlayerFilter) -> bool {
+    // Only a playing (or, if asked for, paused) player can keep the screen awake
+    if !filter.keeps_awake(player.status) {
+        log::trace!("[PLAYBACK] {} is not keeping the screen awake: {:?}", player.name, player.status);
+        return false;
+    }
+
+    // Only read the track's URL if a filter needs it
+    if !filter.needs_track_url() {
+        return true;
+    }
+    let url = get_track_url(conn, &player.name).await;
+
+    // When only video counts, skip the players that are known to be playing audio
+    if filter.video_only && url.as_deref().is_some_and(is_audio_only_url) {
+        log::trace!("[PLAYBACK] {} is playing audio, ignoring it", player.name);
+        return false;
+    }
+
+    // Skip the players playing from an ignored source (e.g. local files)
+    if url.as_deref().is_some_and(|url| filter.ignores_url(url)) {
+        log::trace!("[PLAYBACK] {} is playing from an ignored URL scheme, ignoring it", player.name);
+        return false;
+    }
+
+    true
+}
+
+async fn get_track_url(conn: &Connection, player: &str) -> Option<String> {
+    // Players without usable metadata have no URL, so they are counted (as video) and not ruled out by scheme
+    let metadata = match get_metadata(conn, player).await {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            log::debug!("[PLAYBACK] {} -> Failed to read metadata, counting it: {}", player, e);
+            return None;
+        }
+    };
+
+    // Read the URL of the current track, if the player reports one
+    metadata
+        .get("xesam:url")
+        .and_then(|value| value.downcast_ref::<&str>().ok())
+        .map(str::to_string)
 }
 
+async fn get_metadata(conn: &Connection, player: &str) -> anyhow::Result<HashMap<String, OwnedValue>> {
+    // Get the proxy layer to the player's properties
+    let properties = get_player_properties(conn, player).await?;
 
+    // Get the metadata map of the current track from the player
+    let body = (MEDIA_PLAYER_CONTROL_INTERFACE, "Metadata");
+    let metadata: OwnedValue = properties.call("Get", &body).await?;
+    Ok(metadata.try_into()?)
+}
 
-async fn get_media_player_names(conn: &Connection) -> anyhow::Result<Vec<String>> {
+pub async fn list_media_player_names(conn: &Connection) -> anyhow::Result<Vec<String>> {
     // Wrap the D-Bus daemon in a proxy layer to interface with methods or properties
-    let dbus = Proxy::new(&conn, DBUS_DESTINATION, DBUS_PATH, DBUS_INTERFACE).await?;
+    let dbus = Proxy::new(conn, DBUS_DESTINATION, DBUS_PATH, DBUS_INTERFACE).await?;
 
     // Get the names in the D-Bus
     let names: Vec<String> = dbus.call("ListNames", &()).await?;
 
     // Filter the names of the media players
-    Ok(names
+    Ok(names.into_iter().filter(|name| is_media_player(name)).collect())
+}
+
+pub async fn get_media_player_names(conn: &Connection, filter: &PlayerFilter) -> anyhow::Result<Vec<String>> {
+    // Get the names of every media player in the D-Bus
+    let names = list_media_player_names(conn).await?;
+    Ok(tracked_media_players(conn, names, filter).await)
+}
+
+pub async fn tracked_media_players(conn: &Connection, names: Vec<String>, filter: &PlayerFilter) -> Vec<String> {
+    // Filter the names of the media players the user wants tracked
+    let names: Vec<String> = names
         .into_iter()
-        .filter(|name| is_media_player(name))
-        .collect())
+        .filter(|name| should_track_player(name, filter))
+        .collect();
+
+    // Limit the number of tracked media players before probing them, so an abnormal bus is not probed in full
+    let (names, dropped) = filter.cap_players(names);
+
+    // Only warn when the number of dropped players changes, as this runs on every update
+    if DROPPED_PLAYERS.swap(dropped.len(), Ordering::Relaxed) != dropped.len() && !dropped.is_empty() {
+        log::warn!(
+            "[DISCOVERY] Tracking at most {} media players, ignoring: {}",
+            names.len(),
+            dropped.join(", ")
+        );
+    }
+
+    // Skip names without a working Player object, as they can never report playback
+    let responds = join_all(names.iter().map(|name| has_player_object(conn, name))).await;
+    let media_players: Vec<String> = names
+        .into_iter()
+        .zip(responds)
+        .filter_map(|(name, responds)| responds.then_some(name))
+        .collect();
+
+    media_players
+}
+
+pub async fn get_player_name(conn: &Connection, player: &str) -> String {
+    // Reuse the name read before, as a player does not rename itself
+    if let Some(name) = PLAYER_NAMES.lock().unwrap().get(player) {
+        return name.clone();
+    }
+
+    // Fall back to the bus name (e.g. "chromium.instance1234") if the player does not name itself
+    let name = match get_player_identity(conn, player).await {
+        Ok(identity) => identity.unwrap_or_else(|| player_display_name(player).to_string()),
+        Err(e) => {
+            // The player may be too busy to answer, so ask again next time rather than caching the bus name
+            log::trace!("[DISCOVERY] Failed to read the name of {}: {}", player, e);
+            return player_display_name(player).to_string();
+        }
+    };
+    PLAYER_NAMES.lock().unwrap().insert(player.to_string(), name.clone());
+    name
+}
+
+async fn get_player_identity(conn: &Connection, player: &str) -> anyhow::Result<Option<String>> {
+    // The name of the player is on the root MPRIS interface, which shares the Player's object
+    let properties = get_player_properties(conn, player).await?;
+    let body = (MEDIA_PLAYER_ROOT_INTERFACE, "Identity");
+    let identity: OwnedValue = properties.call("Get", &body).await?;
+
+    // Ignore a blank name, so the player is still shown by its bus name
+    Ok(identity
+        .downcast_ref::<&str>()
+        .ok()
+        .map(|identity| identity.trim().to_string())
+        .filter(|identity| !identity.is_empty()))
 }
 
 async fn get_playback_status(
     conn: &Connection,
     player: &str,
-) -> anyhow::Result<Option<String>> {
-    // Open a proxy layer to the D-Bus to interface with its methods or properties
-    let properties = Proxy::new(&conn, player, MEDIA_PLAYER_PATH, MEDIA_PLAYER_INTERFACE).await?;
+) -> anyhow::Result<Option<PlaybackStatus>> {
+    // Get the proxy layer to the player's properties
+    let properties = get_player_properties(conn, player).await?;
 
     // Get the playback status from the player
-    let body = ("org.mpris.MediaPlayer2.Player", "PlaybackStatus");
+    let body = (MEDIA_PLAYER_CONTROL_INTERFACE, "PlaybackStatus");
     let status: anyhow::Result<OwnedValue, _> = properties.call("Get", &body).await;
 
-    // Check for the existence of the property
+    // Check for the existence of the property, a status that is not a string is not a known status
     match status {
-        Ok(value) => Ok(Some(value.to_string())),
+        Ok(value) => Ok(Some(
+            value
+                .downcast_ref::<&str>()
+                .ok()
+                .map_or(PlaybackStatus::Unknown, parse_playback_status),
+        )),
         Err(_) => Ok(None),
     }
 }
+
+async fn has_player_object(conn: &Connection, player: &str) -> bool {
+    // A player with a cached proxy has already answered
+    if PLAYER_PROXIES.lock().unwrap().contains_key(player) {
+        return true;
+    }
+
+    // Ask the player for its playback status, which any working Player object can answer
+    let properties = match Proxy::new(conn, player.to_string(), MEDIA_PLAYER_PATH, MEDIA_PLAYER_INTERFACE).await {
+        Ok(properties) => properties,
+        Err(e) => {
+            log::debug!("[DISCOVERY] Skipping {}, unable to reach its Player object: {}", player, e);
+            return false;
+        }
+    };
+    let body = (MEDIA_PLAYER_CONTROL_INTERFACE, "PlaybackStatus");
+    if let Err(e) = properties.call::<_, _, OwnedValue>("Get", &body).await {
+        log::debug!("[DISCOVERY] Skipping {}, it has no working Player object: {}", player, e);
+        return false;
+    }
+
+    // Keep the proxy of the player that answered, so it is not probed again
+    PLAYER_PROXIES.lock().unwrap().insert(player.to_string(), properties);
+    true
+}
+
+async fn get_player_properties(conn: &Connection, player: &str) -> anyhow::Result<Proxy<'static>> {
+    // Reuse the proxy from a previous update, so steady-state polling does not set up new proxies
+    if let Some(properties) = PLAYER_PROXIES.lock().unwrap().get(player) {
+        return Ok(properties.clone());
+    }
+
+    // Open a proxy layer to the D-Bus to interface with its methods or properties, which is only kept once
+    // the player has been seen to answer (see has_player_object)
+    Ok(Proxy::new(conn, player.to_string(), MEDIA_PLAYER_PATH, MEDIA_PLAYER_INTERFACE).await?)
+}
+
+pub fn forget_player_proxy(player: &str) {
+    // The player has been removed, so its proxy and name will not be used again
+    PLAYER_PROXIES.lock().unwrap().remove(player);
+    PLAYER_NAMES.lock().unwrap().remove(player);
+}
+
+pub fn clear_player_proxies() {
+    // The proxies are tied to the connection they were opened on, so they cannot outlive it
+    PLAYER_PROXIES.lock().unwrap().clear();
+
+    // A player reached over a new connection may be a different instance under the same bus name
+    PLAYER_NAMES.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{FakePlayer, PrivateBus};
+    use async_std::task;
+
+    fn names(players: &[&str]) -> Vec<String> {
+        players.iter().map(|player| format!("org.mpris.MediaPlayer2.{}", player)).collect()
+    }
+
+    #[test]
+    fn keeps_awake_while_playing() {
+        assert!(PlayerFilter::default().keeps_awake(PlaybackStatus::Playing));
+    }
+
+    #[test]
+    fn keeps_awake_while_paused_only_if_asked() {
+        let filter = PlayerFilter {
+            keep_awake_while_paused: true,
+            ..PlayerFilter::default()
+        };
+        assert!(filter.keeps_awake(PlaybackStatus::Paused));
+        assert!(!PlayerFilter::default().keeps_awake(PlaybackStatus::Paused));
+    }
+
+    #[test]
+    fn never_keeps_awake_while_stopped_or_unknown() {
+        let filter = PlayerFilter {
+            keep_awake_while_paused: true,
+            ..PlayerFilter::default()
+        };
+        for status in [PlaybackStatus::Stopped, PlaybackStatus::Unknown] {
+            assert!(!filter.keeps_awake(status));
+            assert!(!PlayerFilter::default().keeps_awake(status));
+        }
+    }
+
+    #[test]
+    fn cap_players_keeps_every_player_without_a_cap() {
+        let filter = PlayerFilter::default();
+        let (kept, dropped) = filter.cap_players(names(&["vlc", "mpv", "spotify"]));
+        assert_eq!(kept, names(&["vlc", "mpv", "spotify"]));
+        assert!(dropped.is_empty());
+    }
+
+    #[test]
+    fn cap_players_keeps_every_player_within_the_cap() {
+        let filter = PlayerFilter {
+            max_players: Some(3),
+            ..PlayerFilter::default()
+        };
+        let (kept, dropped) = filter.cap_players(names(&["vlc", "mpv", "spotify"]));
+        assert_eq!(kept.len(), 3);
+        assert!(dropped.is_empty());
+    }
+
+    #[test]
+    fn cap_players_drops_by_name() {
+        let filter = PlayerFilter {
+            max_players: Some(2),
+            ..PlayerFilter::default()
+        };
+        let (kept, dropped) = filter.cap_players(names(&["vlc", "mpv", "spotify"]));
+        assert_eq!(kept, names(&["mpv", "spotify"]));
+        assert_eq!(dropped, names(&["vlc"]));
+    }
+
+    #[test]
+    fn cap_players_keeps_allowlisted_players_first() {
+        let filter = PlayerFilter {
+            allowlist: vec!["vlc".to_string(), "firefox".to_string()],
+            max_players: Some(2),
+            ..PlayerFilter::default()
+        };
+        let (kept, dropped) =
+            filter.cap_players(names(&["firefox.instance_1_23", "chromium", "vlc", "firefox.instance_4_56"]));
+        assert_eq!(kept, names(&["vlc", "firefox.instance_1_23"]));
+        assert_eq!(dropped, names(&["firefox.instance_4_56", "chromium"]));
+    }
+
+    #[test]
+    #[ignore = "starts a private dbus-daemon"]
+    fn playing_players_follow_the_playback_status() {
+        task::block_on(async {
+            let bus = PrivateBus::start().unwrap();
+            let conn = bus.connect().await.unwrap();
+            let name = "org.mpris.MediaPlayer2.testplayer";
+            let player = FakePlayer::serve(&bus, name, "Test Player", "Playing").await.unwrap();
+            let cache = PlayingMediaCache::new(Duration::from_secs(60), Arc::new(RwLock::new(PlayerFilter::default())));
+
+            let playing = cache.playing_players(&conn, None).await.unwrap();
+            assert_eq!(
+                playing,
+                vec![PlayerStatus {
+                    name: name.to_string(),
+                    identity: "Test Player".to_string(),
+                    status: PlaybackStatus::Playing,
+                }]
+            );
+
+            // The cached result is reused until it is invalidated, as the playback monitor does on a status change
+            player.set_status("Paused").await.unwrap();
+            assert_eq!(cache.playing_players(&conn, None).await.unwrap(), playing);
+            cache.invalidate();
+            assert!(cache.playing_players(&conn, None).await.unwrap().is_empty());
+
+            player.set_status("Playing").await.unwrap();
+            assert_eq!(playing_players(&conn, &PlayerFilter::default()).await.unwrap(), playing);
+        });
+    }
+
+    #[test]
+    #[ignore = "starts a private dbus-daemon"]
+    fn hung_player_does_not_hide_the_others() {
+        task::block_on(async {
+            let bus = PrivateBus::start().unwrap();
+            let conn = bus.connect().await.unwrap();
+            let (good, hung, gone) = (
+                "org.mpris.MediaPlayer2.goodplayer",
+                "org.mpris.MediaPlayer2.hungplayer",
+                "org.mpris.MediaPlayer2.goneplayer",
+            );
+            let _good = FakePlayer::serve(&bus, good, "Good Player", "Playing").await.unwrap();
+            let _hung = FakePlayer::serve_hung(&bus, hung).await.unwrap();
+
+            // The hung player times out when probed, so it is skipped rather than failing the listing
+            let filter = PlayerFilter::default();
+            let names = list_media_player_names(&conn).await.unwrap();
+            assert_eq!(tracked_media_players(&conn, names, &filter).await, vec![good.to_string()]);
+            let playing = playing_players(&conn, &filter).await.unwrap();
+            assert_eq!(playing.iter().map(|player| player.name.as_str()).collect::<Vec<_>>(), vec![good]);
+
+            // A player that left the bus has no owner, so only the others are mapped
+            let players = [good, hung, gone].map(str::to_string);
+            let owners = get_media_player_owners(&conn, &players).await.unwrap();
+            let mut owned: Vec<&str> = owners.values().map(String::as_str).collect();
+            owned.sort();
+            assert_eq!(owned, vec![good, hung]);
+        });
+    }
+
+    #[test]
+    #[ignore = "starts a private dbus-daemon"]
+    fn players_are_queried_concurrently() {
+        task::block_on(async {
+            const PLAYERS: u32 = 20;
+            const DELAY: Duration = Duration::from_millis(100);
+
+            let bus = PrivateBus::start().unwrap();
+            let conn = bus.connect().await.unwrap();
+            let mut players = Vec::new();
+            for n in 0..PLAYERS {
+                let name = format!("org.mpris.MediaPlayer2.slowplayer{}", n);
+                players.push(FakePlayer::serve_slow(&bus, &name, "Slow Player", "Playing", DELAY).await.unwrap());
+            }
+
+            // Each player is asked for its status twice (probing it, then querying it), which one at a time
+            // would take 2 * PLAYERS * DELAY
+            let started = Instant::now();
+            let playing = playing_players(&conn, &PlayerFilter::default()).await.unwrap();
+            let elapsed = started.elapsed();
+            assert_eq!(playing.len(), PLAYERS as usize);
+            assert!(elapsed < DELAY * 5, "took {:?}", elapsed);
+        });
+    }
+}
@@ -1,4 +1,13 @@
 pub mod application;
+pub mod audio;
+pub mod backends;
+pub mod inhibitors;
+pub mod manager;
 pub mod media_player;
+pub mod notifications;
+pub mod power;
+pub mod schedule;
 pub mod screensaver;
+pub mod session_lock;
+pub mod stats;
 mod monitor;
@@ -1,3 +1,5 @@
+use async_std::channel::TrySendError;
+
 #[derive(Clone)]
 pub struct AppChannel<T> {
     /// The producer channel (sender)
@@ -14,6 +16,12 @@ impl<T> AppChannel<T> {
         Self { producer, consumer }
     }
 
+    pub fn bounded(capacity: usize) -> Self {
+        // Create a channel that holds at most `capacity` signals
+        let (producer, consumer) = async_std::channel::bounded::<T>(capacity);
+        Self { producer, consumer }
+    }
+
     pub fn get_producer(&self) -> async_std::channel::Sender<T> {
         self.producer.clone()
     }
@@ -22,3 +30,13 @@ impl<T> AppChannel<T> {
         self.consumer.clone()
     }
 }
+
+impl AppChannel<()> {
+    pub fn request_refresh(&self) -> anyhow::Result<()> {
+        // A full channel already has a refresh queued, and refreshes are idempotent, so drop this one
+        match self.producer.try_send(()) {
+            Ok(()) | Err(TrySendError::Full(())) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
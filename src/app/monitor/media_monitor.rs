@@ -1,5 +1,6 @@
 use crate::app::application::Application;
-use crate::utils::is_media_player;
+use crate::app::media_player::forget_player_proxy;
+use crate::utils::should_track_player;
 use futures::StreamExt;
 use std::sync::Arc;
 use zbus::fdo::DBusProxy;
@@ -9,7 +10,7 @@ pub struct MediaMonitor {}
 impl MediaMonitor {
     pub async fn start(app: &Arc<Application>) -> anyhow::Result<()> {
         // Extract the D-Bus connection from the app
-        let conn = app.get_connection();
+        let conn = &app.get_connection();
 
         // Create a proxy for the D-Bus interface
         let dbus: DBusProxy = DBusProxy::new(conn).await?;
@@ -31,10 +32,15 @@ impl MediaMonitor {
                 }
             };
 
-            // If the name of the signal is not for a media player
+            // Drop the cached proxy of a removed name, including the players that are not tracked
             let service_name = args.name;
-            if !is_media_player(&service_name) {
-                // Ignore non-media services
+            if args.new_owner.is_none() {
+                forget_player_proxy(&service_name);
+            }
+
+            // If the name of the signal is not for a media player the user wants tracked
+            if !should_track_player(&service_name, &app.get_player_filter()) {
+                // Ignore non-media (and filtered out) services
                 continue;
             }
 
@@ -75,3 +81,54 @@ impl MediaMonitor {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fake_app, FakePlayback, FakePlayer, PrivateBus};
+    use async_std::future::timeout;
+    use async_std::task;
+    use std::time::Duration;
+
+    const SIGNAL_TIMEOUT: Duration = Duration::from_secs(5);
+    const QUIET_PERIOD: Duration = Duration::from_millis(200);
+
+    #[test]
+    #[ignore = "starts a private dbus-daemon"]
+    fn notices_players_being_added_and_removed() {
+        task::block_on(async {
+            let bus = PrivateBus::start().unwrap();
+            let app = fake_app(bus.connect().await.unwrap(), None, &FakePlayback::default());
+            let changes = app.get_media_channel().get_consumer();
+            task::spawn({
+                let app = app.clone();
+                async move { MediaMonitor::start(&app).await }
+            });
+
+            // The monitor subscribes in the background, so add players until it is seen to be listening
+            loop {
+                let warmup = FakePlayer::serve(&bus, "org.mpris.MediaPlayer2.warmup", "Warmup", "Stopped")
+                    .await
+                    .unwrap();
+                let heard = timeout(QUIET_PERIOD, changes.recv()).await.is_ok();
+                drop(warmup);
+                if heard {
+                    break;
+                }
+            }
+            while timeout(QUIET_PERIOD, changes.recv()).await.is_ok() {}
+
+            let player = FakePlayer::serve(&bus, "org.mpris.MediaPlayer2.testplayer", "Test Player", "Playing")
+                .await
+                .unwrap();
+            timeout(SIGNAL_TIMEOUT, changes.recv()).await.unwrap().unwrap();
+            drop(player);
+            timeout(SIGNAL_TIMEOUT, changes.recv()).await.unwrap().unwrap();
+
+            // Services that are not media players do not rebuild the list
+            let other = bus.connect().await.unwrap();
+            other.request_name("org.example.NotAPlayer").await.unwrap();
+            assert!(timeout(QUIET_PERIOD, changes.recv()).await.is_err());
+        });
+    }
+}
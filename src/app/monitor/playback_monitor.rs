@@ -1,9 +1,13 @@
 use crate::app::application::Application;
-use crate::app::media_player::get_media_player_streams;
-use crate::global_constants::UnifiedStream;
-use futures::stream::select_all;
-use futures::StreamExt;
+use crate::app::media_player::{affects_playback, get_media_player_owners, get_media_player_stream, list_media_player_names, tracked_media_players};
+use crate::app::power::{get_battery_stream, read_battery};
+use crate::app::session_lock::{get_lock_stream, is_session_locked, parse_lock_signal};
+use crate::global_constants::{DbusSignalStream, BACKEND_RECHECK_INTERVAL};
+use futures::stream::Fuse;
+use futures::{FutureExt, StreamExt};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Instant;
 use zbus::Connection;
 
 pub struct PlaybackMonitor {}
@@ -11,7 +15,7 @@ pub struct PlaybackMonitor {}
 impl PlaybackMonitor {
     pub async fn start(app: &Arc<Application>) -> anyhow::Result<()> {
         // Extract the D-Bus connection from the app
-        let conn = app.get_connection();
+        let conn = &app.get_connection();
 
         // Extract the screensaver from the app
         let ss = app.get_screensaver();
@@ -20,35 +24,149 @@ impl PlaybackMonitor {
         let mut media_consumer = app.get_media_channel().get_consumer();
         let mut tray_consumer = app.get_tray_channel().get_consumer();
 
-        // Get the UI producer to request the UI be refreshed
-        let ui_producer = app.get_ui_channel().get_producer();
+        // Listen for the session being locked/unlocked, monitoring still works without it
+        let mut lock_stream = match get_lock_stream(conn).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::warn!("[PLAYBACK] Unable to detect the session being locked: {}", e);
+                Box::pin(futures::stream::pending()) as DbusSignalStream
+            }
+        }
+        .fuse();
+
+        // Start in the lock state that the session is currently in
+        let is_locked = is_session_locked(conn).await.unwrap_or(false);
+        ss.set_locked(is_locked);
+
+        // Start in the schedule's current state, re-checking it whenever a window opens or closes
+        Self::check_schedule(app);
+        let mut next_boundary = app.get_schedule().until_next_boundary().map(|until| Instant::now() + until);
+
+        // Listen for the battery changing if a threshold is set, monitoring still works without it
+        let mut battery_stream = Self::battery_stream(app).await.fuse();
+        Self::check_battery(app).await;
 
-        // Initialise the stream with an initial state
-        let mut unified_stream = Self::rebuild_streams(conn).await?;
+        // Initialise the stream with an initial state (no players are listened to while locked)
+        let mut player_stream = if is_locked {
+            Self::unsubscribed()
+        } else {
+            Self::subscribe(conn).await?
+        };
 
-        // Update the state of the application
-        ss.update_state(conn).await?;
+        // Map the senders of the player signals back to the media players they belong to
+        let (media_players, mut player_owners) = Self::list_players(app, conn).await?;
 
-        // Notify the UI of the initial state
-        ui_producer.send(()).await?;
+        // When the player signals are being gathered into one update (None if no update is pending)
+        let mut pending_update: Option<Instant> = None;
+
+        // Update the state of the application, notifying the UI of the initial state
+        app.update_state_for(Some(media_players)).await?;
+
+        // When the players are next re-checked, in case a signal was missed (None if disabled)
+        let mut next_poll = app.get_poll_interval().map(|interval| Instant::now() + interval);
+
+        // When to look for an inhibit backend again, while none is available (None if one is)
+        let mut next_backend_check = (!ss.has_backend()).then(|| Instant::now() + BACKEND_RECHECK_INTERVAL);
 
         // Log that the service is monitoring for playback changes in media players
         log::info!("[PLAYBACK] Media Playback monitor service started");
 
         loop {
+            // Wake up to re-check the state if the screensaver is waiting on a possible duck to end
+            let recheck = Self::wait_until(ss.pending_recheck()).fuse();
+            futures::pin_mut!(recheck);
+
+            // Wake up to run the update for a burst of player signals once the burst has settled
+            let debounce = Self::wait_until(pending_update).fuse();
+            futures::pin_mut!(debounce);
+
+            // Wake up to re-check the players as a safety net for dropped or malformed signals
+            let poll = Self::wait_until(next_poll).fuse();
+            futures::pin_mut!(poll);
+
+            // Wake up when a scheduled window opens or closes, so the state flips without a media event
+            let boundary = Self::wait_until(next_boundary).fuse();
+            futures::pin_mut!(boundary);
+
+            // Wake up to look for an inhibit backend, in case a supported service has started
+            let backend_check = Self::wait_until(next_backend_check).fuse();
+            futures::pin_mut!(backend_check);
+
             // Wait for the first signal to fire then process it.
             futures::select! {
+                // If a pause has lasted long enough that it is no longer a possible duck
+                _ = recheck => {
+                    log::trace!("[PLAYBACK] Re-checking playback after a pause");
+
+                    // Query the players rather than reuse the result from when the pause started
+                    ss.invalidate_media_cache();
+                    pending_update = None;
+                    app.update_state().await?;
+                }
+
+                // If it is time for the periodic re-check of the players
+                _ = poll => {
+                    log::trace!("[PLAYBACK] Periodically re-checking playback");
+                    next_poll = app.get_poll_interval().map(|interval| Instant::now() + interval);
+
+                    // Query the players rather than trust the state built from their signals
+                    ss.invalidate_media_cache();
+                    pending_update = None;
+                    app.update_state().await?;
+                }
+
+                // If a scheduled window has opened or closed
+                _ = boundary => {
+                    next_boundary = app.get_schedule().until_next_boundary().map(|until| Instant::now() + until);
+
+                    // Only update the state if the blocker moved into or out of the scheduled hours
+                    if Self::check_schedule(app) {
+                        ss.invalidate_media_cache();
+                        pending_update = None;
+                        app.update_state().await?;
+                    }
+                }
+
+                // If it is time to look for an inhibit backend again
+                _ = backend_check => {
+                    if app.recheck_backend().await {
+                        // Keep the screen awake for any media that is already playing
+                        next_backend_check = None;
+                        ss.invalidate_media_cache();
+                        pending_update = None;
+                        app.update_state().await?;
+                    } else {
+                        next_backend_check = Some(Instant::now() + BACKEND_RECHECK_INTERVAL);
+                    }
+                }
+
+                // If a burst of player signals has settled
+                _ = debounce => {
+                    log::trace!("[PLAYBACK] Player signals have settled, updating the state");
+
+                    // Run a single update for every signal received during the burst
+                    pending_update = None;
+                    app.update_state().await?;
+                }
+
                 // If a signal has been sent from the media producer (MediaMonitor)
                 _ = media_consumer.select_next_some() => {
                     // Log that the MediaMonitor detected a change
                     log::trace!("[PLAYBACK] MediaMonitor detected a change");
 
+                    // While locked, the players are not listened to, so they are rebuilt on unlock instead
+                    if ss.is_locked() {
+                        log::trace!("[PLAYBACK] Session is locked, deferring the rebuild until unlock");
+                        continue;
+                    }
+
                     // Rebuild the list of media players since a change has been detected
-                    unified_stream = Self::rebuild_streams(conn).await?;
-                    ss.update_state(conn).await?;
+                    let (media_players, owners) = Self::list_players(app, conn).await?;
+                    player_owners = owners;
 
-                    // Request the UI to refresh
-                    ui_producer.send(()).await?;
+                    // Check the playback of the rebuilt list, rather than listing the players again
+                    pending_update = None;
+                    app.update_state_for(Some(media_players)).await?;
                 },
 
                 // If a signal has been sent from the system tray
@@ -56,36 +174,280 @@ impl PlaybackMonitor {
                     // Log that the system tray has asked to refresh state
                     log::trace!("[PLAYBACK] System tray has forced state refresh");
 
+                    // The tray can change which players are tracked, so rebuild the list of media players
+                    let mut media_players = None;
+                    if !ss.is_locked() {
+                        let (players, owners) = Self::list_players(app, conn).await?;
+                        player_owners = owners;
+                        media_players = Some(players);
+                    }
+
+                    // Force the playback status to be queried rather than reused
+                    ss.invalidate_media_cache();
+
                     // Update the state of the application as system tray has forced update
-                    ss.update_state(conn).await?;
+                    pending_update = None;
+                    app.update_state_for(media_players).await?;
+                }
+
+                // If the session has been locked or unlocked
+                signal = lock_stream.select_next_some() => {
+                    // Parse the new lock state from the signal
+                    let is_locked = match parse_lock_signal(&signal) {
+                        Ok(is_locked) => is_locked,
+                        Err(e) => {
+                            log::warn!("[PLAYBACK] Failed to parse the lock state: {}", e);
+                            continue;
+                        }
+                    };
+
+                    // Ignore signals that do not change the lock state
+                    if is_locked == ss.is_locked() {
+                        continue;
+                    }
+                    ss.set_locked(is_locked);
+
+                    let mut media_players = None;
+                    if is_locked {
+                        // Drop the player subscription while locked. Players keep emitting
+                        // PropertiesChanged for Position/Metadata/Volume updates, and each of those
+                        // would otherwise wake this task and query every player over the D-Bus.
+                        // Dropping the stream also removes its match rule from the bus daemon,
+                        // so during a long locked period only the lock signal is delivered.
+                        log::debug!("[PLAYBACK] Session locked, pausing playback monitoring");
+                        player_stream = Self::unsubscribed();
+                    } else {
+                        // Listen to the players again, as they may have changed while locked
+                        log::debug!("[PLAYBACK] Session unlocked, resuming playback monitoring");
+                        player_stream = Self::subscribe(conn).await?;
+                        let (players, owners) = Self::list_players(app, conn).await?;
+                        player_owners = owners;
+                        media_players = Some(players);
+                    }
 
-                    // Request the UI to refresh
-                    ui_producer.send(()).await?;
+                    // Release the inhibit on lock, or re-evaluate the players on unlock
+                    pending_update = None;
+                    app.update_state_for(media_players).await?;
+                }
+
+                // If the power supply or the charge of the battery has changed
+                _ = battery_stream.select_next_some() => {
+                    // Only update the state if the battery crossed the threshold (or the AC was plugged in/out)
+                    if Self::check_battery(app).await {
+                        pending_update = None;
+                        app.update_state().await?;
+                    }
                 }
 
                 // If a signal has been received from an individual media player
-                _ = unified_stream.select_next_some() => {
+                signal = player_stream.select_next_some() => {
+                    // Map the signal back to the media player that sent it
+                    let header = signal.header();
+                    let sender = header.sender().map(|sender| sender.as_str());
+                    let Some(player_name) = sender.and_then(|sender| player_owners.get(sender)) else {
+                        // Ignore signals from objects that are not a known media player
+                        log::trace!("[PLAYBACK] Ignoring signal from unknown sender: {:?}", sender);
+                        continue;
+                    };
+
+                    // Ignore the Position/Metadata/Volume churn that cannot change the state
+                    match affects_playback(&signal, &app.get_player_filter()) {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            log::trace!("[PLAYBACK] Ignoring unrelated property change from {}", player_name);
+                            continue;
+                        }
+                        Err(e) => {
+                            // A signal that cannot be parsed may still be a playback change
+                            log::debug!("[PLAYBACK] Failed to parse the changed properties of {}: {}", player_name, e);
+                        }
+                    }
+
                     // Log that a media player has changed its playback status
-                    log::trace!("[PLAYBACK] Media player has changed its playback status");
+                    log::trace!("[PLAYBACK] {} has changed its playback status", player_name);
 
-                    // Update the state of the application as a state change was detected
-                    ss.update_state(conn).await?;
+                    // The cached result predates the change, so the update must query the players
+                    ss.invalidate_media_cache();
 
-                    // Request the UI to refresh
-                    ui_producer.send(()).await?;
+                    // Without a debounce window, update the state for every signal
+                    let window = app.get_signal_debounce();
+                    if window.is_zero() {
+                        app.update_state().await?;
+                        continue;
+                    }
+
+                    // Otherwise, start gathering the burst (later signals in the window join it)
+                    pending_update.get_or_insert_with(|| Instant::now() + window);
                 }
             }
         }
     }
 
-    async fn rebuild_streams(conn: &Connection) -> anyhow::Result<UnifiedStream> {
-        // Get an updated list of streams
-        let new_streams = get_media_player_streams(&conn).await?;
+    async fn list_players(
+        app: &Arc<Application>,
+        conn: &Connection,
+    ) -> anyhow::Result<(Vec<String>, HashMap<String, String>)> {
+        // List the media players once, sharing the list between the signal mapping, the playback check and the
+        // tray's list of players
+        let names = list_media_player_names(conn).await?;
+        app.set_player_names(names.clone());
+        let media_players = tracked_media_players(conn, names, &app.get_player_filter()).await;
+        let owners = get_media_player_owners(conn, &media_players).await?;
+        Ok((media_players, owners))
+    }
+
+    async fn battery_stream(app: &Arc<Application>) -> DbusSignalStream {
+        // Without a threshold or the system bus, the battery is never checked
+        let pending = || Box::pin(futures::stream::pending()) as DbusSignalStream;
+        let (Some(_), Some(system_conn)) = (app.get_battery_threshold(), app.get_system_connection()) else {
+            return pending();
+        };
+
+        match get_battery_stream(system_conn).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::warn!("[PLAYBACK] Unable to detect changes to the battery: {}", e);
+                pending()
+            }
+        }
+    }
+
+    fn check_schedule(app: &Arc<Application>) -> bool {
+        // Report if the blocker moved into or out of the scheduled hours
+        let ss = app.get_screensaver();
+        let off_schedule = !app.get_schedule().is_active_now();
+        if off_schedule == ss.is_off_schedule() {
+            return false;
+        }
+        ss.set_off_schedule(off_schedule);
+        if off_schedule {
+            log::info!("[PLAYBACK] Outside the scheduled hours, no longer keeping the screen awake");
+        } else {
+            log::info!("[PLAYBACK] Inside the scheduled hours, keeping the screen awake for media again");
+        }
+        true
+    }
+
+    async fn check_battery(app: &Arc<Application>) -> bool {
+        let (Some(threshold), Some(system_conn)) = (app.get_battery_threshold(), app.get_system_connection()) else {
+            return false;
+        };
+
+        // Read the battery, a machine whose battery cannot be read is treated as on AC
+        let is_low = match read_battery(system_conn).await {
+            Ok(battery) => battery.is_below(threshold),
+            Err(e) => {
+                log::debug!("[PLAYBACK] Failed to read the battery: {}", e);
+                false
+            }
+        };
+
+        // Report if the battery crossed the threshold
+        let ss = app.get_screensaver();
+        if is_low == ss.is_battery_low() {
+            return false;
+        }
+        ss.set_battery_low(is_low);
+        if is_low {
+            log::info!("[PLAYBACK] Battery is below {}%, no longer keeping the screen awake", threshold);
+        } else {
+            log::info!("[PLAYBACK] Battery is no longer low, keeping the screen awake again");
+        }
+        true
+    }
+
+    async fn subscribe(conn: &Connection) -> anyhow::Result<Fuse<DbusSignalStream>> {
+        // Listen to the playback changes of all the media players as one stream
+        Ok(get_media_player_stream(conn).await?.fuse())
+    }
+
+    async fn wait_until(deadline: Option<Instant>) {
+        // Without a deadline there is nothing to wait for, so never complete
+        let Some(deadline) = deadline else {
+            return futures::future::pending().await;
+        };
+
+        // Sleep until the deadline has passed
+        async_std::task::sleep(deadline.saturating_duration_since(Instant::now())).await;
+    }
+
+    fn unsubscribed() -> Fuse<DbusSignalStream> {
+        // A stream that never yields, used while the players are not being listened to
+        (Box::pin(futures::stream::pending()) as DbusSignalStream).fuse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::global_constants::SCREENSAVER_DESTINATION;
+    use crate::test_support::{fake_app, FakeBackend, FakePlayback, PrivateBus};
+    use crate::ui::icon_pack::AppIconState;
+    use async_std::future::timeout;
+    use async_std::task;
+    use std::time::Duration;
+
+    const UPDATE_TIMEOUT: Duration = Duration::from_secs(1);
+
+    #[test]
+    #[ignore = "starts a private dbus-daemon"]
+    fn keeps_running_without_a_backend() {
+        task::block_on(async {
+            let bus = PrivateBus::start().unwrap();
+            let playback = FakePlayback::default();
+            playback.set_playing(&["org.mpris.MediaPlayer2.testplayer"]);
+            let app = fake_app(bus.connect().await.unwrap(), None, &playback);
+            let monitor = task::spawn({
+                let app = app.clone();
+                async move { PlaybackMonitor::start(&app).await }
+            });
+
+            // Media is playing, but with nothing to keep the screen awake the blocker stays in the error state
+            task::sleep(Duration::from_millis(300)).await;
+            app.get_media_channel().request_refresh().unwrap();
+            task::sleep(Duration::from_millis(300)).await;
+            assert!(playback.queries() >= 2);
+            assert!(!app.get_screensaver().is_blocked());
+            assert_eq!(AppIconState::for_app(&app), AppIconState::Disconnected);
+
+            // The monitor is still running, rather than having failed the update
+            assert!(monitor.cancel().await.is_none());
+
+            // The backend keeps being looked for, and is picked up once its service appears
+            assert!(!app.recheck_backend().await);
+            let service = bus.connect().await.unwrap();
+            service.request_name(SCREENSAVER_DESTINATION).await.unwrap();
+            assert!(app.recheck_backend().await);
+            assert_eq!(AppIconState::for_app(&app), AppIconState::Inactive);
+        });
+    }
+
+    #[test]
+    #[ignore = "starts a private dbus-daemon"]
+    fn re_enabling_blocks_for_media_already_playing() {
+        task::block_on(async {
+            let bus = PrivateBus::start().unwrap();
+            let (backend, playback) = (FakeBackend::default(), FakePlayback::default());
+            playback.set_playing(&["org.mpris.MediaPlayer2.testplayer"]);
+            let app = fake_app(bus.connect().await.unwrap(), Some(backend.clone()), &playback);
+            app.get_screensaver().disallow_updates();
+
+            // Wait for the monitor's first update, which leaves the screen alone while the blocker is disabled
+            let ui_refreshes = app.get_ui_channel().get_consumer();
+            let monitor = task::spawn({
+                let app = app.clone();
+                async move { PlaybackMonitor::start(&app).await }
+            });
+            timeout(UPDATE_TIMEOUT, ui_refreshes.recv()).await.unwrap().unwrap();
+            assert_eq!(backend.inhibit_count(), 0);
 
-        // Update the unified set of streams with the new list
-        let unified_stream = select_all(new_streams);
+            // Re-enabling updates the state straight away, rather than on the next player signal or poll
+            app.set_enabled(true).await.unwrap();
+            timeout(UPDATE_TIMEOUT, ui_refreshes.recv()).await.unwrap().unwrap();
+            assert!(app.get_screensaver().is_blocked());
+            assert_eq!(backend.inhibit_count(), 1);
 
-        // Return the unified set of streams
-        Ok(unified_stream)
+            monitor.cancel().await;
+        });
     }
 }
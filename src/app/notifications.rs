@@ -0,0 +1,260 @@
+use crate::config::Config;
+use crate::global_constants::{NOTIFICATION_COALESCE_WINDOW, NOTIFICATIONS_DESTINATION, NOTIFICATIONS_INTERFACE, NOTIFICATIONS_PATH};
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use zbus::{Connection, Proxy};
+use zvariant::Value;
+
+// The kinds of notification that can be individually enabled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotificationCategory {
+    /// The screen started being kept awake
+    Block,
+
+    /// The screen stopped being kept awake
+    Unblock,
+
+    /// Something went wrong that stops the blocker from working
+    Error,
+}
+
+impl NotificationCategory {
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "block" => Some(NotificationCategory::Block),
+            "unblock" => Some(NotificationCategory::Unblock),
+            "error" => Some(NotificationCategory::Error),
+            _ => None,
+        }
+    }
+}
+
+// The urgency levels defined by the freedesktop notification spec
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotificationUrgency {
+    Low,
+    Normal,
+    Critical,
+}
+
+impl NotificationUrgency {
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "low" => Some(NotificationUrgency::Low),
+            "normal" => Some(NotificationUrgency::Normal),
+            "critical" => Some(NotificationUrgency::Critical),
+            _ => None,
+        }
+    }
+
+    fn level(&self) -> u8 {
+        match self {
+            NotificationUrgency::Low => 0,
+            NotificationUrgency::Normal => 1,
+            NotificationUrgency::Critical => 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct NotificationSettings {
+    /// The urgency the notifications are sent with
+    pub urgency: NotificationUrgency,
+
+    /// How long the notifications are shown for in milliseconds (-1 for the server default, 0 for sticky)
+    pub timeout_ms: i32,
+
+    /// The categories of notification that are sent (none by default)
+    pub categories: Vec<NotificationCategory>,
+}
+
+impl NotificationSettings {
+    pub fn from_config(config: &Config) -> Self {
+        // The switch turns every notification off, otherwise only the listed categories are sent
+        let categories = if config.notifications {
+            config
+                .notify_categories
+                .iter()
+                .filter_map(|name| {
+                    let category = NotificationCategory::parse(name);
+                    if category.is_none() {
+                        log::warn!("[SYSTEM] Unknown notification category: {}", name);
+                    }
+                    category
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        // Read the urgency, falling back to low so the notifications are not intrusive
+        let urgency = NotificationUrgency::parse(&config.notify_urgency).unwrap_or_else(|| {
+            log::warn!("[SYSTEM] Invalid notification urgency: {}, using low", config.notify_urgency);
+            NotificationUrgency::Low
+        });
+
+        Self {
+            urgency,
+            timeout_ms: config.notify_timeout_ms,
+            categories,
+        }
+    }
+
+    pub fn is_enabled(&self, category: NotificationCategory) -> bool {
+        self.categories.contains(&category)
+    }
+}
+
+pub struct Notifier {
+    /// The settings the notifications are sent with (replaced when the config is reloaded)
+    settings: RwLock<NotificationSettings>,
+
+    /// The ID of the last notification sent, so a new one replaces it rather than stacking up (0 if none)
+    last_id: AtomicU32,
+
+    /// Counts the block/unblock transitions, so a transition superseded within the window is not sent
+    transitions: AtomicU64,
+
+    /// The last block/unblock transition sent, so flips that end where they started send nothing (None if none)
+    last_transition: Mutex<Option<NotificationCategory>>,
+}
+
+impl Notifier {
+    pub fn new(settings: NotificationSettings) -> Self {
+        Self {
+            settings: RwLock::new(settings),
+            last_id: AtomicU32::new(0),
+            transitions: AtomicU64::new(0),
+            last_transition: Mutex::new(None),
+        }
+    }
+
+    pub fn apply_settings(&self, settings: NotificationSettings) {
+        // Notifications already sent keep their settings, the new ones are used from the next notification
+        *self.settings.write().unwrap() = settings;
+    }
+
+    pub async fn notify_transition(
+        &self,
+        conn: &Connection,
+        category: NotificationCategory,
+        summary: &str,
+        body: &str,
+    ) -> anyhow::Result<()> {
+        // Wait for the state to settle, dropping this notification if another transition happens meanwhile
+        let transition = self.transitions.fetch_add(1, Ordering::SeqCst) + 1;
+        async_std::task::sleep(NOTIFICATION_COALESCE_WINDOW).await;
+        if self.transitions.load(Ordering::SeqCst) != transition {
+            return Ok(());
+        }
+
+        // Skip the notification if the state settled where the last notification left it
+        if self.last_transition.lock().unwrap().replace(category) == Some(category) {
+            return Ok(());
+        }
+
+        self.notify(conn, category, summary, body).await
+    }
+
+    pub async fn notify(
+        &self,
+        conn: &Connection,
+        category: NotificationCategory,
+        summary: &str,
+        body: &str,
+    ) -> anyhow::Result<()> {
+        // Skip the notification if its category has not been enabled
+        let settings = self.settings.read().unwrap().clone();
+        if !settings.is_enabled(category) {
+            return Ok(());
+        }
+
+        // Open a new proxy to the notification server
+        let notifications = Proxy::new(
+            conn,
+            NOTIFICATIONS_DESTINATION,
+            NOTIFICATIONS_PATH,
+            NOTIFICATIONS_INTERFACE,
+        )
+        .await?;
+
+        // Set the urgency of the notification through its hints
+        let mut hints: HashMap<&str, Value> = HashMap::new();
+        hints.insert("urgency", Value::U8(settings.urgency.level()));
+
+        // Send the notification, replacing the previous one
+        let actions: Vec<&str> = Vec::new();
+        let replaces_id = self.last_id.load(Ordering::SeqCst);
+        let id: u32 = notifications
+            .call(
+                "Notify",
+                &(
+                    "MediaBlocker",
+                    replaces_id,
+                    "",
+                    summary,
+                    body,
+                    actions,
+                    hints,
+                    settings.timeout_ms,
+                ),
+            )
+            .await?;
+
+        // Store the ID so the next notification replaces this one
+        self.last_id.store(id, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notifications_are_off_by_default() {
+        let settings = NotificationSettings::from_config(&Config::default());
+        assert!(settings.categories.is_empty());
+        assert_eq!(settings.urgency, NotificationUrgency::Low);
+        assert_eq!(settings.timeout_ms, -1);
+    }
+
+    #[test]
+    fn enabled_notifications_send_the_listed_categories() {
+        let config = Config {
+            notifications: true,
+            notify_categories: vec!["Unblock".to_string(), "error".to_string(), "long-block".to_string()],
+            notify_urgency: "critical".to_string(),
+            notify_timeout_ms: 0,
+            ..Config::default()
+        };
+        let settings = NotificationSettings::from_config(&config);
+
+        // Unknown categories are skipped, the others are matched regardless of case
+        assert_eq!(settings.categories, vec![NotificationCategory::Unblock, NotificationCategory::Error]);
+        assert!(!settings.is_enabled(NotificationCategory::Block));
+        assert_eq!(settings.urgency, NotificationUrgency::Critical);
+        assert_eq!(settings.timeout_ms, 0);
+    }
+
+    #[test]
+    fn invalid_urgency_falls_back_to_low() {
+        let config = Config {
+            notify_urgency: "loud".to_string(),
+            ..Config::default()
+        };
+        assert_eq!(NotificationSettings::from_config(&config).urgency, NotificationUrgency::Low);
+    }
+
+    #[test]
+    fn applied_settings_replace_the_previous_ones() {
+        let notifier = Notifier::new(NotificationSettings::from_config(&Config::default()));
+        let config = Config {
+            notifications: true,
+            ..Config::default()
+        };
+        notifier.apply_settings(NotificationSettings::from_config(&config));
+        assert!(notifier.settings.read().unwrap().is_enabled(NotificationCategory::Block));
+    }
+}
@@ -0,0 +1,56 @@
+use crate::global_constants::{DbusSignalStream, DBUS_PROPERTIES_INTERFACE, UPOWER_DESTINATION, UPOWER_DEVICE_INTERFACE, UPOWER_DISPLAY_DEVICE_PATH, UPOWER_INTERFACE, UPOWER_PATH};
+use futures::StreamExt;
+use zbus::message::Type;
+use zbus::{Connection, MatchRule, MessageStream, Proxy};
+
+// The power supply of the machine, as reported by UPower
+#[derive(Clone, Copy, Debug)]
+pub struct BatteryState {
+    /// Indicate if the machine is running on its battery rather than on AC
+    pub on_battery: bool,
+
+    /// The charge of the battery from 0 to 100 (the combined charge if there are several)
+    pub percentage: f64,
+}
+
+impl BatteryState {
+    pub fn is_below(&self, threshold_percent: f64) -> bool {
+        // A machine on AC never counts as low, however empty its battery is
+        self.on_battery && self.percentage < threshold_percent
+    }
+}
+
+pub async fn get_battery_stream(system_conn: &Connection) -> anyhow::Result<DbusSignalStream> {
+    // Match the property changes of UPower and its devices with one rule (OnBattery, Percentage)
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .interface(DBUS_PROPERTIES_INTERFACE)?
+        .member("PropertiesChanged")?
+        .path_namespace(UPOWER_PATH)?
+        .build();
+
+    // Listen for any signals matching the rule
+    let stream = MessageStream::for_match_rule(rule, system_conn, None).await?;
+
+    // Drop any messages that failed to be received
+    let stream = stream.filter_map(|message| async move { message.ok() });
+    Ok(Box::pin(stream) as DbusSignalStream)
+}
+
+pub async fn read_battery(system_conn: &Connection) -> anyhow::Result<BatteryState> {
+    // Ask UPower if the machine is running on its battery
+    let upower = Proxy::new(system_conn, UPOWER_DESTINATION, UPOWER_PATH, UPOWER_INTERFACE).await?;
+    let on_battery: bool = upower.get_property("OnBattery").await?;
+
+    // Read the charge of the display device, which combines every battery into one
+    let display_device = Proxy::new(
+        system_conn,
+        UPOWER_DESTINATION,
+        UPOWER_DISPLAY_DEVICE_PATH,
+        UPOWER_DEVICE_INTERFACE,
+    )
+    .await?;
+    let percentage: f64 = display_device.get_property("Percentage").await?;
+
+    Ok(BatteryState { on_battery, percentage })
+}
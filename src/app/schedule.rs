@@ -0,0 +1,101 @@
+use crate::utils::local_now;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+// The minutes in a day, as the windows repeat every day
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+// A daily window of time, in minutes since midnight (the end is exclusive)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeWindow {
+    /// When the window opens
+    start: u32,
+
+    /// When the window closes, before the start if the window wraps past midnight
+    end: u32,
+}
+
+impl TimeWindow {
+    pub fn parse(window: &str) -> Option<Self> {
+        // Windows are written as "HH:MM-HH:MM", e.g. "22:00-02:00"
+        let (start, end) = window.split_once('-')?;
+        Some(Self {
+            start: parse_time(start)?,
+            end: parse_time(end)?,
+        })
+    }
+
+    pub fn contains(&self, minute: u32) -> bool {
+        match self.start.cmp(&self.end) {
+            std::cmp::Ordering::Less => self.start <= minute && minute < self.end,
+            // A window that wraps past midnight is open late in the day and early the next
+            std::cmp::Ordering::Greater => minute >= self.start || minute < self.end,
+            // A window that closes when it opens covers the whole day
+            std::cmp::Ordering::Equal => true,
+        }
+    }
+}
+
+// When the blocker is allowed to keep the screen awake
+#[derive(Clone, Debug, Default)]
+pub struct Schedule {
+    /// The windows the blocker is active in (always active if empty)
+    windows: Vec<TimeWindow>,
+}
+
+impl Schedule {
+    pub fn parse(windows: &[String]) -> Self {
+        // Skip any window that cannot be parsed, rather than refusing to start
+        let windows = windows
+            .iter()
+            .filter_map(|window| {
+                let parsed = TimeWindow::parse(window);
+                if parsed.is_none() {
+                    log::warn!("[SCHEDULE] Ignoring invalid window: {} (expected HH:MM-HH:MM)", window);
+                }
+                parsed
+            })
+            .collect();
+        Self { windows }
+    }
+
+    pub fn is_always(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    pub fn is_active_now(&self) -> bool {
+        let minute = minute_of_day(local_now());
+        self.is_always() || self.windows.iter().any(|window| window.contains(minute))
+    }
+
+    pub fn until_next_boundary(&self) -> Option<Duration> {
+        // Without windows the blocker never changes with the time
+        if self.is_always() {
+            return None;
+        }
+
+        // Find the next time a window opens or closes, at the start of its minute
+        let now = local_now();
+        let minute = minute_of_day(now);
+        let minutes = self
+            .windows
+            .iter()
+            .flat_map(|window| [window.start, window.end])
+            .map(|boundary| match (boundary + MINUTES_PER_DAY - minute) % MINUTES_PER_DAY {
+                0 => MINUTES_PER_DAY,
+                minutes => minutes,
+            })
+            .min()?;
+        Some(Duration::from_secs(u64::from(minutes) * 60 - u64::from(now.second())))
+    }
+}
+
+fn parse_time(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.trim().split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+fn minute_of_day(now: OffsetDateTime) -> u32 {
+    u32::from(now.hour()) * 60 + u32::from(now.minute())
+}
@@ -1,46 +1,489 @@
-use crate::app::media_player::any_playing_media;
-use crate::global_constants::{SCREENSAVER_DESTINATION, SCREENSAVER_INTERFACE, SCREENSAVER_PATH};
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-use std::sync::Arc;
+use crate::app::backends::{InhibitBackend, InhibitHandle};
+use crate::app::inhibitors::{list_inhibitors, Inhibitor};
+use crate::app::media_player::{get_track_metadata, PlaybackSource, PlaybackStatus, PlayerStatus, PlayingMediaCache, SharedPlayerFilter};
+use crate::app::stats::StatsCollector;
+use crate::config::Config;
+use crate::global_constants::{LOGIN1_DESTINATION, LOGIN1_INTERFACE, LOGIN1_PATH, KDE_INHIBIT_POLICIES, KDE_POWER_MANAGEMENT_DESTINATION, KDE_POWER_MANAGEMENT_INTERFACE, KDE_POWER_MANAGEMENT_PATH, INHIBIT_RETRY_DELAY, MEDIA_STATUS_CACHE_TTL};
+use crate::utils::{env_flag, is_kde_desktop, player_display_name};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 use zbus::{Connection, Proxy};
+use zvariant::OwnedFd;
+
+// Bits of the screensaver flags, packed together so they can be read as one consistent snapshot
+const UPDATES_ALLOWED: u8 = 1 << 0;
+const BLOCKED: u8 = 1 << 1;
+const MANUAL_HOLD: u8 = 1 << 2;
+const BATTERY_LOW: u8 = 1 << 3;
+const OFF_SCHEDULE: u8 = 1 << 4;
+
+// The state of the blocker, as shown to the user
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScreensaverStatus {
+    /// The blocker has been disabled by the user
+    Disabled,
+
+    /// The blocker is enabled, but nothing is keeping the screen awake
+    Idle,
+
+    /// The blocker is enabled, but the battery is too low to keep the screen awake
+    BatteryLow,
+
+    /// The blocker is enabled, but it is outside the hours it is scheduled for
+    OffSchedule,
+
+    /// The blocker is keeping the screen awake
+    Blocking,
+}
+
+impl ScreensaverStatus {
+    pub fn is_enabled(&self) -> bool {
+        *self != ScreensaverStatus::Disabled
+    }
+
+    pub fn is_blocking(&self) -> bool {
+        *self == ScreensaverStatus::Blocking
+    }
+}
 
 pub struct ScreensaverState {
-    /// Indicate if the screensaver can allow block/unblock updates
-    allow_updates: Arc<AtomicBool>,
+    /// The desktop service used to take and release the inhibit (None until a supported service is available)
+    backend: RwLock<Option<Arc<dyn InhibitBackend>>>,
+
+    /// Indicate if the user has been warned that the screen cannot be kept awake without a backend
+    backend_warned: AtomicBool,
+
+    /// The application name shown by the desktop for our inhibits (changed when the config is reloaded)
+    inhibit_app_name: RwLock<String>,
+
+    /// The reason shown by the desktop for our inhibits (changed when the config is reloaded)
+    inhibit_reason: RwLock<String>,
+
+    /// Indicate if the screensaver can allow block/unblock updates (UPDATES_ALLOWED), if it is
+    /// currently being blocked for media (BLOCKED), if the user is keeping it awake (MANUAL_HOLD), if the
+    /// battery is too low to keep it awake (BATTERY_LOW), and if it is outside the scheduled hours (OFF_SCHEDULE)
+    flags: AtomicU8,
+
+    /// Handle to the inhibit taken by the user regardless of the media (None if not held)
+    manual_handle: Mutex<Option<InhibitHandle>>,
+
+    /// Handles to the inhibits taken by the backend, keyed by the media player each was taken for
+    inhibit_handles: Mutex<HashMap<String, InhibitHandle>>,
+
+    /// Indicate if KDE's PowerManagement inhibit is taken alongside the screensaver inhibit
+    kde_quirks: bool,
+
+    /// Unique ID for the inhibit entry stored by KDE's PowerManagement (None if not inhibited, as 0 is a valid ID)
+    kde_cookie: Mutex<Option<u32>>,
 
-    /// Indicate if the screensaver is currently being blocked
-    blocked: Arc<AtomicBool>,
+    /// Connection to the system bus used to inhibit suspend alongside the screensaver (None if not inhibiting suspend)
+    suspend_conn: Option<Connection>,
 
-    /// Unique ID for the inhibit entry stored by KDE for the blocked screensaver (0 if unblocked)
-    inhibit_cookie: Arc<AtomicU32>,
+    /// The fd of the logind sleep inhibitor, which is held until it is closed (None if not inhibited)
+    suspend_fd: Mutex<Option<OwnedFd>>,
+
+    /// Indicate if the user's session is currently locked
+    locked: Arc<AtomicBool>,
+
+    /// Where the playing media players are queried from, reusing a recent result during signal storms
+    media_cache: Box<dyn PlaybackSource>,
+
+    /// How long media must stay stopped before unblocking, so gaps between tracks and seeking are ignored (zero if
+    /// disabled), which the tray also holds the active icon for
+    unblock_grace: RwLock<Duration>,
+
+    /// When the media was first seen to have stopped while blocked (None if playing or not blocked)
+    stopped_since: Mutex<Option<Instant>>,
+
+    /// How long a player can pause for without releasing its inhibit, so ducking by a call is ignored (None if disabled)
+    ducking_threshold: RwLock<Option<Duration>>,
+
+    /// When each player holding an inhibit was first seen to have paused, keyed by the player
+    paused_since: Mutex<HashMap<String, Instant>>,
+
+    /// How long a player must keep playing before it keeps the screen awake (None to block straight away)
+    min_play: RwLock<Option<Duration>>,
+
+    /// When each playing media player was first seen to be playing, keyed by the player
+    playing_since: Mutex<HashMap<String, Instant>>,
+
+    /// When the next player will have played for long enough to keep the screen awake (None if none is waiting)
+    eligible_at: Mutex<Option<Instant>>,
+
+    /// When to try again to take the inhibits that failed (None if none failed)
+    retry_at: Mutex<Option<Instant>>,
+
+    /// Held while taking or releasing inhibits, so a shutdown cannot interleave with an update
+    update_lock: async_std::sync::Mutex<()>,
+
+    /// Totals up how long the screen has been kept awake for media
+    stats: StatsCollector,
 }
 
 impl ScreensaverState {
-    pub fn new() -> Self {
+    pub fn new(
+        backend: Option<Box<dyn InhibitBackend>>,
+        system_conn: Option<Connection>,
+        config: &Config,
+        player_filter: SharedPlayerFilter,
+    ) -> Self {
+        // Query the media players over the D-Bus session, caching the result for a moment
+        let media_cache = PlayingMediaCache::new(MEDIA_STATUS_CACHE_TTL, player_filter);
+        Self::with_playback_source(backend, system_conn, config, Box::new(media_cache))
+    }
+
+    pub fn with_playback_source(
+        backend: Option<Box<dyn InhibitBackend>>,
+        system_conn: Option<Connection>,
+        config: &Config,
+        media_cache: Box<dyn PlaybackSource>,
+    ) -> Self {
+        if config.inhibit_suspend && system_conn.is_none() {
+            log::warn!("[SCREENSAVER] Suspend cannot be inhibited, as the system bus is unavailable");
+        }
+
+        match &backend {
+            Some(backend) => log::info!("[SCREENSAVER] Using the {} inhibit backend", backend.name()),
+            None => log::warn!(
+                "[SCREENSAVER] No inhibit backend is available, as the desktop has none of the supported services \
+                 (org.freedesktop.ScreenSaver, org.gnome.SessionManager or logind). The screen cannot be kept awake \
+                 until one appears"
+            ),
+        }
+
         Self {
-            allow_updates: Arc::new(AtomicBool::new(true)),
-            blocked: Arc::new(AtomicBool::new(false)),
-            inhibit_cookie: Arc::new(AtomicU32::new(0)),
+            backend: RwLock::new(backend.map(Arc::from)),
+            backend_warned: AtomicBool::new(false),
+            inhibit_app_name: RwLock::new(config.inhibit_app_name.clone()),
+            inhibit_reason: RwLock::new(config.inhibit_reason.clone()),
+            flags: AtomicU8::new(if config.enabled_on_start { UPDATES_ALLOWED } else { 0 }),
+            manual_handle: Mutex::new(None),
+            inhibit_handles: Mutex::new(HashMap::new()),
+            kde_quirks: env_flag("MEDIABLOCKER_KDE_QUIRKS").unwrap_or_else(is_kde_desktop),
+            kde_cookie: Mutex::new(None),
+            suspend_conn: system_conn.filter(|_| config.inhibit_suspend),
+            suspend_fd: Mutex::new(None),
+            locked: Arc::new(AtomicBool::new(false)),
+            media_cache,
+            unblock_grace: RwLock::new(Duration::from_secs(config.unblock_grace_seconds)),
+            stopped_since: Mutex::new(None),
+            ducking_threshold: RwLock::new(ducking_threshold(config)),
+            paused_since: Mutex::new(HashMap::new()),
+            min_play: RwLock::new(min_play(config)),
+            playing_since: Mutex::new(HashMap::new()),
+            eligible_at: Mutex::new(None),
+            retry_at: Mutex::new(None),
+            update_lock: async_std::sync::Mutex::new(()),
+            stats: StatsCollector::load(),
         }
     }
 
+    #[cfg(test)]
+    pub(crate) fn for_tests(
+        backend: Option<Box<dyn InhibitBackend>>,
+        config: &Config,
+        media_cache: Box<dyn PlaybackSource>,
+    ) -> Self {
+        // Keep the extra inhibits and the statistics away from the desktop the tests run on
+        let state = Self::with_playback_source(backend, None, config, media_cache);
+        Self { kde_quirks: false, stats: StatsCollector::in_memory(), ..state }
+    }
+
+    pub fn apply_config(&self, config: &Config) {
+        // Inhibits already held keep their name and reason, the new ones are used from the next inhibit
+        *self.inhibit_app_name.write().unwrap() = config.inhibit_app_name.clone();
+        *self.inhibit_reason.write().unwrap() = config.inhibit_reason.clone();
+        *self.unblock_grace.write().unwrap() = Duration::from_secs(config.unblock_grace_seconds);
+        *self.ducking_threshold.write().unwrap() = ducking_threshold(config);
+        *self.min_play.write().unwrap() = min_play(config);
+    }
+
+    pub fn unblock_grace(&self) -> Duration {
+        *self.unblock_grace.read().unwrap()
+    }
+
+    fn app_name(&self) -> String {
+        self.inhibit_app_name.read().unwrap().clone()
+    }
+
+    fn reason(&self) -> String {
+        self.inhibit_reason.read().unwrap().clone()
+    }
+
     pub fn allow_updates(&self) {
-        self.allow_updates.store(true, Ordering::Release);
+        // A result cached while disabled may be stale, so the next update must query the players
+        self.media_cache.invalidate();
+        self.set_flag(UPDATES_ALLOWED, true);
     }
 
     pub fn disallow_updates(&self) {
-        self.allow_updates.store(false, Ordering::Release);
+        self.set_flag(UPDATES_ALLOWED, false);
     }
 
     pub fn are_updates_allowed(&self) -> bool {
-        self.allow_updates.load(Ordering::SeqCst)
+        self.flags.load(Ordering::SeqCst) & UPDATES_ALLOWED != 0
     }
 
     pub fn is_blocked(&self) -> bool {
-        self.blocked.load(Ordering::SeqCst)
+        self.flags.load(Ordering::SeqCst) & BLOCKED != 0
+    }
+
+    pub fn status(&self) -> ScreensaverStatus {
+        // Read both flags at once, so an update in between cannot give an inconsistent state
+        let flags = self.flags.load(Ordering::SeqCst);
+        if flags & MANUAL_HOLD != 0 {
+            // The user is keeping the screen awake, regardless of the media or the toggle
+            ScreensaverStatus::Blocking
+        } else if flags & UPDATES_ALLOWED == 0 {
+            ScreensaverStatus::Disabled
+        } else if flags & OFF_SCHEDULE != 0 {
+            ScreensaverStatus::OffSchedule
+        } else if flags & BATTERY_LOW != 0 {
+            ScreensaverStatus::BatteryLow
+        } else if flags & BLOCKED != 0 {
+            ScreensaverStatus::Blocking
+        } else {
+            ScreensaverStatus::Idle
+        }
+    }
+
+    pub fn backend_name(&self) -> Option<&'static str> {
+        self.backend().map(|backend| backend.name())
+    }
+
+    pub fn has_backend(&self) -> bool {
+        self.backend.read().unwrap().is_some()
+    }
+
+    pub fn set_backend(&self, backend: Box<dyn InhibitBackend>) {
+        log::info!("[SCREENSAVER] Found the {} inhibit backend, the screen can now be kept awake", backend.name());
+        *self.backend.write().unwrap() = Some(Arc::from(backend));
+
+        // Warn again if the backend is ever lost
+        self.backend_warned.store(false, Ordering::SeqCst);
+    }
+
+    fn backend(&self) -> Option<Arc<dyn InhibitBackend>> {
+        // Clone the backend out of the lock, so the lock is not held while waiting on D-Bus
+        self.backend.read().unwrap().clone()
+    }
+
+    pub fn is_manually_held(&self) -> bool {
+        self.flags.load(Ordering::SeqCst) & MANUAL_HOLD != 0
+    }
+
+    pub async fn set_manual_hold(&self, conn: &Connection, hold: bool) -> anyhow::Result<()> {
+        // Only one change can take or release inhibits at a time
+        let _guard = self.update_lock.lock().await;
+
+        // Nothing to do if the hold is already in the requested state
+        if hold == self.is_manually_held() {
+            return Ok(());
+        }
+
+        // Without a backend the screen cannot be kept awake
+        let Some(backend) = self.backend() else {
+            anyhow::bail!("No inhibit backend is available to keep the screen awake");
+        };
+
+        if hold {
+            // Take an inhibit that the media updates do not release
+            let app_name = self.app_name();
+            let reason = format!("{} (Keep Awake Now)", self.reason());
+            let handle = backend.inhibit(conn, &app_name, &reason).await?;
+            *self.manual_handle.lock().unwrap() = Some(handle);
+            self.set_flag(MANUAL_HOLD, true);
+
+            // Also stop the session from sleeping, where the screensaver inhibit alone does not
+            self.take_extra_inhibits(conn, &app_name).await;
+            log::debug!("[SCREENSAVER] Keeping the screen awake until the hold is released");
+        } else {
+            // Release the hold, keeping the extra inhibits if media is still keeping the screen awake
+            let handle = self.manual_handle.lock().unwrap().take();
+            self.set_flag(MANUAL_HOLD, false);
+            if !self.is_blocked() {
+                self.release_extra_inhibits(conn).await;
+            }
+            if let Some(handle) = handle {
+                backend.uninhibit(conn, handle).await?;
+            }
+            log::debug!("[SCREENSAVER] Released the hold on the screen");
+        }
+
+        Ok(())
+    }
+
+    pub fn get_stats(&self) -> &StatsCollector {
+        &self.stats
+    }
+
+    fn set_blocked(&self, blocked: bool) {
+        // Time how long the screen is kept awake for media alongside the flag
+        self.set_flag(BLOCKED, blocked);
+        self.stats.record(blocked);
     }
 
-    pub async fn update_state(&self, conn: &Connection) -> anyhow::Result<()> {
+    fn set_flag(&self, flag: u8, set: bool) {
+        if set {
+            self.flags.fetch_or(flag, Ordering::SeqCst);
+        } else {
+            self.flags.fetch_and(!flag, Ordering::SeqCst);
+        }
+    }
+
+    pub fn blocking_players(&self) -> Vec<String> {
+        // List the media players an inhibit is currently held for
+        let mut players: Vec<String> = self.inhibit_handles.lock().unwrap().keys().cloned().collect();
+        players.sort();
+        players
+    }
+
+    pub fn uses_kde_quirks(&self) -> bool {
+        self.kde_quirks
+    }
+
+    pub fn set_locked(&self, locked: bool) {
+        self.locked.store(locked, Ordering::SeqCst);
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::SeqCst)
+    }
+
+    pub fn set_battery_low(&self, low: bool) {
+        self.set_flag(BATTERY_LOW, low);
+    }
+
+    pub fn is_battery_low(&self) -> bool {
+        self.flags.load(Ordering::SeqCst) & BATTERY_LOW != 0
+    }
+
+    pub fn set_off_schedule(&self, off_schedule: bool) {
+        self.set_flag(OFF_SCHEDULE, off_schedule);
+    }
+
+    pub fn is_off_schedule(&self) -> bool {
+        self.flags.load(Ordering::SeqCst) & OFF_SCHEDULE != 0
+    }
+
+    pub fn pending_recheck(&self) -> Option<Instant> {
+        // When holding on through the grace period, the state must be re-checked once it has passed
+        let grace = self.unblock_grace();
+        let grace_recheck = self
+            .stopped_since
+            .lock()
+            .unwrap()
+            .filter(|_| !grace.is_zero())
+            .map(|stopped_since| stopped_since + grace);
+
+        // When ignoring a possible duck, the state must be re-checked once the player has paused for too long
+        let now = Instant::now();
+        let duck_recheck = self.ducking_threshold.read().unwrap().and_then(|threshold| {
+            self.paused_since
+                .lock()
+                .unwrap()
+                .values()
+                .map(|paused_since| *paused_since + threshold)
+                .filter(|at| *at > now)
+                .min()
+        });
+
+        // When an inhibit failed to be taken, the state must be re-checked to try again
+        let retry_at = *self.retry_at.lock().unwrap();
+
+        // When a player has not yet played for long enough, the state must be re-checked once it has
+        let eligible_at = *self.eligible_at.lock().unwrap();
+
+        // Re-check at whichever comes first
+        [grace_recheck, duck_recheck, retry_at, eligible_at].into_iter().flatten().min()
+    }
+
+    pub fn invalidate_media_cache(&self) {
+        self.media_cache.invalidate();
+    }
+
+    pub fn forget_inhibits(&self) {
+        // Inhibits taken on a dropped connection were released when it closed, so only clear their state
+        self.inhibit_handles.lock().unwrap().clear();
+        *self.manual_handle.lock().unwrap() = None;
+        self.set_flag(MANUAL_HOLD, false);
+        *self.kde_cookie.lock().unwrap() = None;
+        *self.suspend_fd.lock().unwrap() = None;
+        self.set_blocked(false);
+        *self.stopped_since.lock().unwrap() = None;
+        self.paused_since.lock().unwrap().clear();
+        self.media_cache.invalidate();
+    }
+
+    pub async fn force_unblock(&self, conn: &Connection) -> anyhow::Result<()> {
+        // Release the inhibit regardless of whether media is playing (after any update in flight)
+        let _guard = self.update_lock.lock().await;
+        self.unblock(conn).await?;
+        log::debug!("[SCREENSAVER] Forced into the UNBLOCKED state");
+        Ok(())
+    }
+
+    pub async fn shutdown(&self, conn: &Connection) -> anyhow::Result<()> {
+        // Stop the monitors from taking a new inhibit while the app is exiting
+        self.disallow_updates();
+
+        // Release the inhibit regardless of the flags (a no-op when no inhibit is held), waiting for
+        // any update in flight so an inhibit it takes is not left behind
+        let _guard = self.update_lock.lock().await;
+        self.release_manual_hold(conn).await;
+        self.unblock(conn).await?;
+        log::debug!("[SCREENSAVER] Released the inhibit for shutdown");
+        Ok(())
+    }
+
+    pub async fn release_orphaned_inhibitors(
+        &self,
+        conn: &Connection,
+        system_conn: Option<&Connection>,
+    ) -> anyhow::Result<Vec<Inhibitor>> {
+        // List every idle inhibitor the backend knows about
+        let inhibitors = list_inhibitors(conn, system_conn, self.kde_quirks).await?;
+
+        // Only show the inhibitors that were taken under our (configured) application name
+        let app_name = self.app_name();
+        let ours: Vec<Inhibitor> = inhibitors
+            .iter()
+            .filter(|inhibitor| inhibitor.app_name == app_name)
+            .cloned()
+            .collect();
+        log::info!(
+            "[SCREENSAVER] Found {} inhibitors, {} held by MediaBlocker",
+            inhibitors.len(),
+            ours.len()
+        );
+        for inhibitor in &ours {
+            log::info!("[SCREENSAVER] - {}: {}", inhibitor.app_name, inhibitor.reason);
+        }
+
+        // Both KDE and logind drop the inhibitors of a process once it exits, so any of ours still
+        // listed are held by this process. Release them, the next update re-inhibits if media is playing.
+        if !ours.is_empty() {
+            self.force_unblock(conn).await?;
+        }
+
+        Ok(ours)
+    }
+
+    pub async fn update_state(
+        &self,
+        conn: &Connection,
+        media_players: Option<Vec<String>>,
+    ) -> anyhow::Result<Option<Vec<PlayerStatus>>> {
+        // Returns the players found keeping the screen awake (None if the players were not queried)
+        // Only one update can take or release inhibits at a time
+        let _guard = self.update_lock.lock().await;
+
+        // This update tries every inhibit again, so any pending retry is no longer needed
+        *self.retry_at.lock().unwrap() = None;
+        *self.eligible_at.lock().unwrap() = None;
+
         // If the screensaver disallows updates
         if !self.are_updates_allowed() {
             // If the screensaver is currently blocked
@@ -49,90 +492,695 @@ impl ScreensaverState {
                 self.unblock(conn).await?;
             }
 
+            // The players are not watched meanwhile, so they cannot be known to have kept playing
+            self.playing_since.lock().unwrap().clear();
+
             // Return early
-            return Ok(());
+            return Ok(None);
         }
 
-        // Check if any media is currently playing
-        let is_media_playing = any_playing_media(conn).await?;
+        // If the session is locked, nobody is watching the media
+        if self.is_locked() {
+            // If the screensaver is currently blocked
+            if self.is_blocked() {
+                // Unblock the screensaver so the locked session can go to sleep
+                self.unblock(conn).await?;
+                log::debug!("[SCREENSAVER] Session locked, now in the UNBLOCKED state");
+            }
 
-        // Check if the screensaver is currently being blocked
-        let is_screensaver_blocked = self.is_blocked();
+            // The players are not watched meanwhile, so they cannot be known to have kept playing
+            self.playing_since.lock().unwrap().clear();
 
-        // If media is playing, and the screensaver is not being blocked
-        if is_media_playing && !is_screensaver_blocked {
-            self.block(conn).await?;
-            log::debug!("[SCREENSAVER] Now in the BLOCKED state");
-            return Ok(());
+            // Return early
+            return Ok(None);
         }
 
-        // If media is not playing, and the screensaver is being blocked
-        if !is_media_playing && is_screensaver_blocked {
+        // Outside the scheduled hours the screen is never kept awake, as if the blocker were disabled
+        if self.is_off_schedule() {
+            // If the screensaver is currently blocked
+            if self.is_blocked() {
+                // Unblock the screensaver as the schedule has ended
+                self.unblock(conn).await?;
+                log::debug!("[SCREENSAVER] Outside the scheduled hours, now in the UNBLOCKED state");
+            }
+
+            // The players are not watched meanwhile, so they cannot be known to have kept playing
+            self.playing_since.lock().unwrap().clear();
+
+            // Return early
+            return Ok(None);
+        }
+
+        // If the battery is low, let the machine sleep rather than drain it
+        if self.is_battery_low() {
+            // If the screensaver is currently blocked
+            if self.is_blocked() {
+                // Unblock the screensaver so the machine can sleep before the battery runs out
+                self.unblock(conn).await?;
+                log::debug!("[SCREENSAVER] Battery low, now in the UNBLOCKED state");
+            }
+
+            // The players are not watched meanwhile, so they cannot be known to have kept playing
+            self.playing_since.lock().unwrap().clear();
+
+            // Return early
+            return Ok(None);
+        }
+
+        // Get the media players that are currently playing (reusing a recent result during signal storms)
+        let snapshot = self.media_cache.playing_players(conn, media_players).await?;
+        let playing: Vec<String> = snapshot.iter().map(|player| player.name.clone()).collect();
+
+        // Get the media players that an inhibit is currently held for
+        let blocking = self.blocking_players();
+
+        // Calls and notifications duck players by pausing them momentarily, so a player that paused within the
+        // ducking threshold keeps its inhibit
+        let playing = self.ignore_ducking(conn, playing, &blocking).await;
+
+        // If no media is playing, and the screensaver is being blocked
+        if playing.is_empty() && !blocking.is_empty() {
+            // Gaps between tracks and seeking stop the media briefly, so hold on through the grace period
+            if self.is_within_grace() {
+                log::trace!("[SCREENSAVER] Media stopped briefly, waiting for the grace period to pass");
+                return Ok(Some(snapshot));
+            }
+
             self.unblock(conn).await?;
-            log::debug!("[SCREENSAVER] Now in the UNBLOCKED state");
-            return Ok(());
+            self.playing_since.lock().unwrap().clear();
+            log::info!("[SCREENSAVER] UNBLOCKED because {} stopped playing", blocking.join(", "));
+            return Ok(Some(snapshot));
         }
 
-        Ok(())
+        // The media is playing again (or the screen is not blocked), so any pause has ended
+        *self.stopped_since.lock().unwrap() = None;
+
+        // Only players that have kept playing for long enough can take an inhibit, so a notification sound
+        // or a short stinger does not keep the screen awake
+        let eligible = self.eligible_players(&playing);
+
+        // Take an inhibit for each of the players that started playing (before releasing any, so the
+        // screen is never left unblocked while switching between players)
+        // A failed inhibit (e.g. the service did not reply in time) is tried again later, rather than
+        // stopping the monitor
+        for player in eligible.iter().filter(|player| !blocking.contains(player)) {
+            if let Err(e) = self.block_player(conn, player).await {
+                log::warn!("[SCREENSAVER] Failed to take the inhibit for {}, retrying later: {}", player, e);
+                *self.retry_at.lock().unwrap() = Some(Instant::now() + INHIBIT_RETRY_DELAY);
+                continue;
+            }
+
+            // Only ask the player for its track when the transition will be logged
+            if log::log_enabled!(log::Level::Info) {
+                log::info!("[SCREENSAVER] BLOCKING because {} is playing{}", player, describe_track(conn, player).await);
+            }
+        }
+
+        // Release the inhibits of the players that are no longer playing
+        for player in blocking.iter().filter(|player| !playing.contains(player)) {
+            self.unblock_player(conn, player).await?;
+            log::info!("[SCREENSAVER] Released the inhibit for {}, as it stopped playing", player);
+        }
+
+        Ok(Some(snapshot))
+    }
+
+    fn eligible_players(&self, playing: &[String]) -> Vec<String> {
+        // Without a minimum every playing player can keep the screen awake
+        let Some(min_play) = *self.min_play.read().unwrap() else {
+            return playing.to_vec();
+        };
+
+        // Start timing the players that started playing, forgetting those that stopped (cancelling their wait)
+        let now = Instant::now();
+        let mut playing_since = self.playing_since.lock().unwrap();
+        playing_since.retain(|player, _| playing.contains(player));
+        for player in playing {
+            playing_since.entry(player.clone()).or_insert(now);
+        }
+
+        // Keep the players that have played for long enough, re-checking when the next one will have
+        let mut eligible = Vec::new();
+        let mut eligible_at = self.eligible_at.lock().unwrap();
+        for (player, since) in playing_since.iter() {
+            let at = *since + min_play;
+            if at <= now {
+                eligible.push(player.clone());
+            } else {
+                log::trace!("[SCREENSAVER] {} has not played for long enough to keep the screen awake", player);
+                *eligible_at = Some(eligible_at.map_or(at, |eligible_at| eligible_at.min(at)));
+            }
+        }
+        eligible
     }
 
-    async fn block(&self, conn: &Connection) -> anyhow::Result<()> {
-        // Check if the inhibit cookie is set
-        if self.inhibit_cookie.load(Ordering::SeqCst) != 0 {
-            // Return that the screen is already being blocked
+    fn is_within_grace(&self) -> bool {
+        // Without a grace period every stop releases the inhibit
+        let grace = self.unblock_grace();
+        if grace.is_zero() {
+            return false;
+        }
+
+        // Start timing the stop the first time the media is seen to have stopped
+        let mut stopped_since = self.stopped_since.lock().unwrap();
+        let since = *stopped_since.get_or_insert_with(Instant::now);
+
+        // The stop is only real once it has lasted longer than the grace period
+        since.elapsed() < grace
+    }
+
+    async fn ignore_ducking(&self, conn: &Connection, mut playing: Vec<String>, blocking: &[String]) -> Vec<String> {
+        // Without a threshold every pause is treated as a real pause
+        let Some(threshold) = *self.ducking_threshold.read().unwrap() else {
+            self.paused_since.lock().unwrap().clear();
+            return playing;
+        };
+
+        // Only a player holding an inhibit that reports being paused can be ducked, a player that stopped
+        // or left the bus releases its inhibit straight away
+        let mut paused = Vec::new();
+        for player in blocking.iter().filter(|player| !playing.contains(player)) {
+            if self.media_cache.player_status(conn, player).await == PlaybackStatus::Paused {
+                paused.push(player.clone());
+            }
+        }
+
+        // Forget the pauses that have ended, then start timing each pause the first time the player is seen
+        // to have paused, treating it as still playing until the pause has lasted longer than the threshold
+        let mut paused_since = self.paused_since.lock().unwrap();
+        paused_since.retain(|player, _| paused.contains(player));
+        let now = Instant::now();
+        for player in paused {
+            let since = *paused_since.entry(player.clone()).or_insert(now);
+            if now < since + threshold {
+                log::trace!("[SCREENSAVER] {} paused briefly, treating it as ducking", player);
+                playing.push(player);
+            }
+        }
+        playing
+    }
+
+    async fn block_player(&self, conn: &Connection, player: &str) -> anyhow::Result<()> {
+        // Check if an inhibit is already held for the player
+        if self.inhibit_handles.lock().unwrap().contains_key(player) {
+            // Return that the screen is already being blocked for the player
             return Ok(());
         }
 
-        // Open a new proxy to the screensaver
-        let screensaver = Proxy::new(
-            conn,
-            SCREENSAVER_DESTINATION,
-            SCREENSAVER_PATH,
-            SCREENSAVER_INTERFACE,
-        )
-        .await?;
+        // Without a backend the screen cannot be kept awake, so warn once rather than on every update
+        let Some(backend) = self.backend() else {
+            if !self.backend_warned.swap(true, Ordering::SeqCst) {
+                log::warn!("[SCREENSAVER] Media is playing, but no inhibit backend is available to keep the screen awake");
+            }
+            return Ok(());
+        };
+
+        // Define the application name and reason for blocking, naming the player in the reason
+        let app_name = self.app_name();
+        let reason = format!("{} ({})", self.reason(), player_display_name(player));
 
-        // Define the application name and reason for blocking
-        let app_name = "Rust Media Monitor".to_string();
-        let reason = "Media is currently playing".to_string();
+        // Ask the backend to inhibit the screen
+        let handle = backend.inhibit(conn, &app_name, &reason).await?;
 
-        // Call the inhibit method to block the screen
-        let cookie: u32 = screensaver.call("Inhibit", &(app_name, reason)).await?;
+        // Store the handle against the player
+        self.inhibit_handles.lock().unwrap().insert(player.to_string(), handle);
+        self.set_blocked(true);
 
-        // Store the cookie globally
-        self.inhibit_cookie.store(cookie, Ordering::SeqCst);
-        self.blocked.store(true, Ordering::SeqCst);
+        // Also stop the session from sleeping, where the screensaver inhibit alone does not
+        self.take_extra_inhibits(conn, &app_name).await;
 
         // Return that the screen is currently being blocked
         Ok(())
     }
 
+    async fn unblock_player(&self, conn: &Connection, player: &str) -> anyhow::Result<()> {
+        // Take the player's handle, and check if it was the last inhibit being held
+        let (handle, is_last) = {
+            let mut handles = self.inhibit_handles.lock().unwrap();
+            let handle = handles.remove(player);
+            (handle, handles.is_empty())
+        };
+
+        // Once the last inhibit is released, the screen is no longer blocked
+        if is_last {
+            self.release_extra_inhibits(conn).await;
+            self.set_blocked(false);
+        }
+
+        // Ask the backend to release the player's inhibit (if any)
+        if let (Some(handle), Some(backend)) = (handle, self.backend()) {
+            backend.uninhibit(conn, handle).await?;
+        }
+
+        Ok(())
+    }
+
     async fn unblock(&self, conn: &Connection) -> anyhow::Result<()> {
-        // Load the cookie, then clear its state
-        let cookie = self.inhibit_cookie.swap(0, Ordering::SeqCst);
+        // Release the KDE PowerManagement and suspend inhibits (if any) alongside the screensaver inhibits
+        self.release_extra_inhibits(conn).await;
+
+        // The screen is no longer blocked, so there is no pause being timed
+        *self.stopped_since.lock().unwrap() = None;
+        self.paused_since.lock().unwrap().clear();
 
-        // If the cookie's value is 0, the screen is not currently being blocked
-        if cookie == 0 {
-            // So, do nothing
+        // Take every handle, then clear their state
+        let handles: Vec<(String, InhibitHandle)> = self.inhibit_handles.lock().unwrap().drain().collect();
+        self.set_blocked(false);
+
+        // If there is no backend, no inhibit can have been taken
+        let Some(backend) = self.backend() else {
             return Ok(());
+        };
+
+        // Ask the backend to release each inhibit, trying every one before reporting a failure
+        let mut result = Ok(());
+        for (player, handle) in handles {
+            if let Err(e) = backend.uninhibit(conn, handle).await {
+                log::warn!("[SCREENSAVER] Failed to release the inhibit for {}: {}", player, e);
+                result = Err(e);
+            }
+        }
+
+        // Return whether the screen is no longer being blocked
+        result
+    }
+
+    async fn release_manual_hold(&self, conn: &Connection) {
+        // Take the handle, then release it through the backend (if any)
+        let handle = self.manual_handle.lock().unwrap().take();
+        self.set_flag(MANUAL_HOLD, false);
+        if let (Some(handle), Some(backend)) = (handle, self.backend())
+            && let Err(e) = backend.uninhibit(conn, handle).await
+        {
+            log::warn!("[SCREENSAVER] Failed to release the hold on the screen: {}", e);
+        }
+    }
+
+    async fn take_extra_inhibits(&self, conn: &Connection, app_name: &str) {
+        // The screensaver inhibit is already held, so a failure here is not fatal. On KDE, the screensaver
+        // inhibit alone does not reliably stop the session from sleeping
+        if self.kde_quirks
+            && let Err(e) = self.kde_block(conn, app_name, &self.reason()).await
+        {
+            log::warn!("[SCREENSAVER] Failed to inhibit KDE PowerManagement: {}", e);
+        }
+
+        // The screensaver inhibit does not stop the machine from suspending, so take a logind sleep inhibitor too
+        if let Err(e) = self.suspend_block(app_name).await {
+            log::warn!("[SCREENSAVER] Failed to inhibit suspend: {}", e);
+        }
+    }
+
+    async fn release_extra_inhibits(&self, conn: &Connection) {
+        // The user's hold keeps the extra inhibits, so they are released along with the hold
+        if self.is_manually_held() {
+            return;
         }
 
-        // Since the cookie has a value here, it means the screen is currently being blocked
-        let screensaver = Proxy::new(
+        // A KDE inhibit that cannot be released is not fatal, as the screensaver inhibits are released regardless
+        if let Err(e) = self.kde_unblock(conn).await {
+            log::warn!("[SCREENSAVER] Failed to release KDE PowerManagement inhibit: {}", e);
+        }
+
+        // logind releases the sleep inhibitor once its fd is closed, which dropping does
+        *self.suspend_fd.lock().unwrap() = None;
+    }
+
+    async fn suspend_block(&self, app_name: &str) -> anyhow::Result<()> {
+        // Only inhibit suspend if the user asked for it, and the system bus is available
+        let Some(system_conn) = &self.suspend_conn else {
+            return Ok(());
+        };
+
+        // Check if the sleep inhibitor is already held
+        if self.suspend_fd.lock().unwrap().is_some() {
+            return Ok(());
+        }
+
+        // Open a new proxy to the logind manager on the system bus
+        let manager = Proxy::new(system_conn, LOGIN1_DESTINATION, LOGIN1_PATH, LOGIN1_INTERFACE).await?;
+
+        // Take a blocking sleep and idle inhibitor, which is held for as long as the returned fd is open
+        let fd: OwnedFd = manager
+            .call("Inhibit", &("sleep:idle", app_name, self.reason(), "block"))
+            .await?;
+        *self.suspend_fd.lock().unwrap() = Some(fd);
+        Ok(())
+    }
+
+    // KDE (Plasma) exposes org.freedesktop.ScreenSaver, but its Inhibit only reliably stops the screen
+    // from locking, and with some reason strings PowerDevil still dims the screen or suspends the
+    // session. Taking an inhibition on KDE's PowerManagement policy agent as well reliably prevents
+    // sleep. This is enabled when XDG_CURRENT_DESKTOP contains KDE, and can be forced on or off with
+    // MEDIABLOCKER_KDE_QUIRKS=1/0.
+    async fn kde_block(&self, conn: &Connection, app_name: &str, reason: &str) -> anyhow::Result<()> {
+        // Check if the KDE cookie is set
+        if self.kde_cookie.lock().unwrap().is_some() {
+            return Ok(());
+        }
+
+        // Open a new proxy to KDE's PowerManagement policy agent
+        let power_management = Proxy::new(
             conn,
-            SCREENSAVER_DESTINATION,
-            SCREENSAVER_PATH,
-            SCREENSAVER_INTERFACE,
+            KDE_POWER_MANAGEMENT_DESTINATION,
+            KDE_POWER_MANAGEMENT_PATH,
+            KDE_POWER_MANAGEMENT_INTERFACE,
         )
         .await?;
 
-        // Remove the inhibit cookie and unblock the screen
-        screensaver
-            .call::<&str, _, ()>("UnInhibit", &(cookie))
+        // Inhibit the policies that would sleep the session or turn off the screen
+        let cookie: u32 = power_management
+            .call("AddInhibition", &(KDE_INHIBIT_POLICIES, app_name, reason))
             .await?;
-        self.blocked.store(false, Ordering::SeqCst);
 
-        // Return that the screen is no longer being blocked
+        // Store the cookie for releasing the inhibition later
+        *self.kde_cookie.lock().unwrap() = Some(cookie);
+        Ok(())
+    }
+
+    async fn kde_unblock(&self, conn: &Connection) -> anyhow::Result<()> {
+        // Load the KDE cookie, then clear its state
+        let cookie = self.kde_cookie.lock().unwrap().take();
+
+        // Without a cookie, KDE's PowerManagement is not being inhibited
+        let Some(cookie) = cookie else {
+            return Ok(());
+        };
+
+        // Open a new proxy to KDE's PowerManagement policy agent
+        let power_management = Proxy::new(
+            conn,
+            KDE_POWER_MANAGEMENT_DESTINATION,
+            KDE_POWER_MANAGEMENT_PATH,
+            KDE_POWER_MANAGEMENT_INTERFACE,
+        )
+        .await?;
+
+        // Release the inhibition
+        power_management
+            .call::<_, _, ()>("ReleaseInhibition", &(cookie))
+            .await?;
         Ok(())
     }
 }
+
+async fn describe_track(conn: &Connection, player: &str) -> String {
+    // Name the track in the transition's log line, leaving it out if the player does not report one
+    match get_track_metadata(conn, player).await {
+        Ok(track) => track.title.map(|title| format!(" (\"{}\")", title)).unwrap_or_default(),
+        Err(e) => {
+            log::trace!("[SCREENSAVER] Failed to read the track of {}: {}", player, e);
+            String::new()
+        }
+    }
+}
+
+fn ducking_threshold(config: &Config) -> Option<Duration> {
+    // A threshold of zero treats every pause as a real pause
+    Some(Duration::from_millis(config.ducking_threshold_ms)).filter(|threshold| !threshold.is_zero())
+}
+
+fn min_play(config: &Config) -> Option<Duration> {
+    // A minimum of zero blocks as soon as a player starts playing
+    Some(Duration::from_secs(config.min_play_seconds)).filter(|min_play| !min_play.is_zero())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{p2p_connection, BackendCall, FakeBackend, FakeKdePowerManagement, FakePlayback, PrivateBus};
+    use async_std::task;
+
+    const SPOTIFY: &str = "org.mpris.MediaPlayer2.spotify";
+
+    fn fake_state(config: &Config, backend: &FakeBackend, playback: &FakePlayback) -> ScreensaverState {
+        ScreensaverState::for_tests(Some(Box::new(backend.clone())), config, Box::new(playback.clone()))
+    }
+
+    #[test]
+    fn blocks_when_media_starts_playing() {
+        task::block_on(async {
+            let (_server, conn) = p2p_connection().await.unwrap();
+            let (backend, playback) = (FakeBackend::default(), FakePlayback::default());
+            let state = fake_state(&Config::default(), &backend, &playback);
+
+            playback.set_playing(&[SPOTIFY]);
+            state.update_state(&conn, None).await.unwrap();
+
+            assert!(state.is_blocked());
+            assert_eq!(state.status(), ScreensaverStatus::Blocking);
+            assert_eq!(state.blocking_players(), vec![SPOTIFY.to_string()]);
+            assert_eq!(
+                backend.calls(),
+                vec![BackendCall::Inhibit { reason: format!("{} (spotify)", Config::default().inhibit_reason), cookie: 1 }]
+            );
+        });
+    }
+
+    #[test]
+    fn does_not_block_twice_for_the_same_player() {
+        task::block_on(async {
+            let (_server, conn) = p2p_connection().await.unwrap();
+            let (backend, playback) = (FakeBackend::default(), FakePlayback::default());
+            let state = fake_state(&Config::default(), &backend, &playback);
+
+            playback.set_playing(&[SPOTIFY]);
+            state.update_state(&conn, None).await.unwrap();
+            state.update_state(&conn, None).await.unwrap();
+
+            assert!(state.is_blocked());
+            assert_eq!(backend.inhibit_count(), 1);
+            assert_eq!(backend.uninhibit_count(), 0);
+        });
+    }
+
+    #[test]
+    fn unblocks_when_media_stops_playing() {
+        task::block_on(async {
+            let (_server, conn) = p2p_connection().await.unwrap();
+            let (backend, playback) = (FakeBackend::default(), FakePlayback::default());
+            let state = fake_state(&Config::default(), &backend, &playback);
+
+            playback.set_playing(&[SPOTIFY]);
+            state.update_state(&conn, None).await.unwrap();
+            playback.set_playing(&[]);
+            state.update_state(&conn, None).await.unwrap();
+
+            assert!(!state.is_blocked());
+            assert_eq!(state.status(), ScreensaverStatus::Idle);
+            assert!(state.blocking_players().is_empty());
+            assert_eq!(backend.calls().last(), Some(&BackendCall::Uninhibit { cookie: 1 }));
+        });
+    }
+
+    #[test]
+    fn stays_blocked_when_media_stops_within_the_grace_period() {
+        task::block_on(async {
+            let (_server, conn) = p2p_connection().await.unwrap();
+            let (backend, playback) = (FakeBackend::default(), FakePlayback::default());
+            let config = Config { unblock_grace_seconds: 5, ..Config::default() };
+            let state = fake_state(&config, &backend, &playback);
+
+            // A gap between tracks reports Stopped before the next track starts Playing
+            for playing in [&[SPOTIFY][..], &[], &[SPOTIFY]] {
+                playback.set_playing(playing);
+                state.update_state(&conn, None).await.unwrap();
+                assert!(state.is_blocked());
+            }
+
+            assert_eq!(backend.inhibit_count(), 1);
+            assert_eq!(backend.uninhibit_count(), 0);
+            assert_eq!(state.pending_recheck(), None);
+        });
+    }
+
+    #[test]
+    fn unblocks_once_the_stop_outlasts_the_grace_period() {
+        task::block_on(async {
+            let (_server, conn) = p2p_connection().await.unwrap();
+            let (backend, playback) = (FakeBackend::default(), FakePlayback::default());
+            let config = Config { unblock_grace_seconds: 1, ..Config::default() };
+            let state = fake_state(&config, &backend, &playback);
+
+            playback.set_playing(&[SPOTIFY]);
+            state.update_state(&conn, None).await.unwrap();
+            playback.set_playing(&[]);
+            state.update_state(&conn, None).await.unwrap();
+            assert!(state.is_blocked());
+
+            // The monitor wakes up to re-check once the grace period has passed
+            let recheck = state.pending_recheck().unwrap();
+            task::sleep(recheck.saturating_duration_since(Instant::now())).await;
+            state.update_state(&conn, None).await.unwrap();
+            assert!(!state.is_blocked());
+            assert_eq!(backend.uninhibit_count(), 1);
+        });
+    }
+
+    #[test]
+    fn keeps_the_inhibit_through_a_pause_shorter_than_the_ducking_threshold() {
+        task::block_on(async {
+            let (_server, conn) = p2p_connection().await.unwrap();
+            let (backend, playback) = (FakeBackend::default(), FakePlayback::default());
+            let config = Config { ducking_threshold_ms: 60_000, ..Config::default() };
+            let state = fake_state(&config, &backend, &playback);
+
+            // A call ducks the player by pausing it, then resumes it
+            for (playing, paused) in [(&[SPOTIFY][..], &[][..]), (&[], &[SPOTIFY]), (&[SPOTIFY], &[])] {
+                playback.set_playing(playing);
+                playback.set_paused(paused);
+                state.update_state(&conn, None).await.unwrap();
+                assert_eq!(state.blocking_players(), vec![SPOTIFY.to_string()]);
+            }
+
+            assert_eq!(backend.inhibit_count(), 1);
+            assert_eq!(backend.uninhibit_count(), 0);
+        });
+    }
+
+    #[test]
+    fn releases_the_inhibit_once_the_pause_outlasts_the_ducking_threshold() {
+        task::block_on(async {
+            let (_server, conn) = p2p_connection().await.unwrap();
+            let (backend, playback) = (FakeBackend::default(), FakePlayback::default());
+            let config = Config { ducking_threshold_ms: 200, ..Config::default() };
+            let state = fake_state(&config, &backend, &playback);
+
+            playback.set_playing(&[SPOTIFY]);
+            state.update_state(&conn, None).await.unwrap();
+            playback.set_playing(&[]);
+            playback.set_paused(&[SPOTIFY]);
+            state.update_state(&conn, None).await.unwrap();
+            assert!(state.is_blocked());
+
+            // The monitor wakes up to re-check once the pause is too long to be ducking
+            let recheck = state.pending_recheck().unwrap();
+            task::sleep(recheck.saturating_duration_since(Instant::now())).await;
+            state.update_state(&conn, None).await.unwrap();
+            assert!(!state.is_blocked());
+            assert_eq!(backend.uninhibit_count(), 1);
+        });
+    }
+
+    #[test]
+    fn releases_an_inhibit_with_a_cookie_of_zero() {
+        task::block_on(async {
+            let (_server, conn) = p2p_connection().await.unwrap();
+            let (backend, playback) = (FakeBackend::with_first_cookie(0), FakePlayback::default());
+            let state = fake_state(&Config::default(), &backend, &playback);
+
+            // A cookie of 0 is held like any other, so it is neither taken again nor skipped when releasing
+            playback.set_playing(&[SPOTIFY]);
+            state.update_state(&conn, None).await.unwrap();
+            state.update_state(&conn, None).await.unwrap();
+            assert!(state.is_blocked());
+            playback.set_playing(&[]);
+            state.update_state(&conn, None).await.unwrap();
+
+            assert!(!state.is_blocked());
+            assert_eq!(backend.inhibit_count(), 1);
+            assert_eq!(backend.calls().last(), Some(&BackendCall::Uninhibit { cookie: 0 }));
+        });
+    }
+
+    #[test]
+    fn releases_the_inhibit_straight_away_when_a_player_stops_despite_the_ducking_threshold() {
+        task::block_on(async {
+            let (_server, conn) = p2p_connection().await.unwrap();
+            let (backend, playback) = (FakeBackend::default(), FakePlayback::default());
+            let config = Config { ducking_threshold_ms: 60_000, ..Config::default() };
+            let state = fake_state(&config, &backend, &playback);
+
+            // Stopping (or leaving the bus) is not ducking, so it is not timed
+            playback.set_playing(&[SPOTIFY]);
+            state.update_state(&conn, None).await.unwrap();
+            playback.set_playing(&[]);
+            state.update_state(&conn, None).await.unwrap();
+
+            assert!(!state.is_blocked());
+            assert_eq!(backend.uninhibit_count(), 1);
+            assert_eq!(state.pending_recheck(), None);
+        });
+    }
+
+    #[test]
+    fn retries_a_failed_inhibit_later() {
+        task::block_on(async {
+            let (_server, conn) = p2p_connection().await.unwrap();
+            let (backend, playback) = (FakeBackend::default(), FakePlayback::default());
+            let state = fake_state(&Config::default(), &backend, &playback);
+
+            backend.set_failing(true);
+            playback.set_playing(&[SPOTIFY]);
+            state.update_state(&conn, None).await.unwrap();
+
+            assert!(!state.is_blocked());
+            assert!(state.pending_recheck().is_some());
+
+            backend.set_failing(false);
+            state.update_state(&conn, None).await.unwrap();
+
+            assert!(state.is_blocked());
+            assert_eq!(state.pending_recheck(), None);
+        });
+    }
+
+    #[test]
+    fn does_nothing_while_updates_are_disallowed() {
+        task::block_on(async {
+            let (_server, conn) = p2p_connection().await.unwrap();
+            let (backend, playback) = (FakeBackend::default(), FakePlayback::default());
+            let state = fake_state(&Config::default(), &backend, &playback);
+            state.disallow_updates();
+
+            playback.set_playing(&[SPOTIFY]);
+            assert_eq!(state.update_state(&conn, None).await.unwrap(), None);
+
+            assert!(!state.is_blocked());
+            assert_eq!(state.status(), ScreensaverStatus::Disabled);
+            assert_eq!(playback.queries(), 0);
+            assert!(backend.calls().is_empty());
+        });
+    }
+
+    #[test]
+    fn unblocks_when_updates_are_disallowed_while_blocked() {
+        task::block_on(async {
+            let (_server, conn) = p2p_connection().await.unwrap();
+            let (backend, playback) = (FakeBackend::default(), FakePlayback::default());
+            let state = fake_state(&Config::default(), &backend, &playback);
+
+            playback.set_playing(&[SPOTIFY]);
+            state.update_state(&conn, None).await.unwrap();
+            state.disallow_updates();
+            state.update_state(&conn, None).await.unwrap();
+
+            assert!(!state.is_blocked());
+            assert_eq!(backend.uninhibit_count(), 1);
+        });
+    }
+
+    #[test]
+    #[ignore = "starts a private dbus-daemon"]
+    fn holds_and_releases_a_kde_cookie_of_zero() {
+        task::block_on(async {
+            let bus = PrivateBus::start().unwrap();
+            let conn = bus.connect().await.unwrap();
+            let kde = FakeKdePowerManagement::serve(&bus).await.unwrap();
+            let (backend, playback) = (FakeBackend::default(), FakePlayback::default());
+            let state = ScreensaverState { kde_quirks: true, ..fake_state(&Config::default(), &backend, &playback) };
+
+            // A cookie of 0 is held like any other, so it is not taken again on the next update
+            playback.set_playing(&[SPOTIFY]);
+            state.update_state(&conn, None).await.unwrap();
+            state.update_state(&conn, None).await.unwrap();
+            assert_eq!(*state.kde_cookie.lock().unwrap(), Some(0));
+            assert_eq!(kde.added(), 1);
+
+            playback.set_playing(&[]);
+            state.update_state(&conn, None).await.unwrap();
+            assert_eq!(*state.kde_cookie.lock().unwrap(), None);
+            assert_eq!(kde.released(), vec![0]);
+        });
+    }
+}
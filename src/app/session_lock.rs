@@ -0,0 +1,36 @@
+use crate::global_constants::{DbusSignalStream, SCREENSAVER_DESTINATION, SCREENSAVER_INTERFACE, SCREENSAVER_PATH};
+use zbus::{Connection, Message, Proxy};
+
+pub async fn get_lock_stream(conn: &Connection) -> anyhow::Result<DbusSignalStream> {
+    // Open a proxy to the screensaver, which owns the lock screen
+    let screensaver = Proxy::new(
+        conn,
+        SCREENSAVER_DESTINATION,
+        SCREENSAVER_PATH,
+        SCREENSAVER_INTERFACE,
+    )
+    .await?;
+
+    // Listen for the screensaver (and therefore the lock screen) becoming active/inactive
+    let stream = screensaver.receive_signal("ActiveChanged").await?;
+    Ok(Box::pin(stream) as DbusSignalStream)
+}
+
+pub async fn is_session_locked(conn: &Connection) -> anyhow::Result<bool> {
+    // Open a proxy to the screensaver, which owns the lock screen
+    let screensaver = Proxy::new(
+        conn,
+        SCREENSAVER_DESTINATION,
+        SCREENSAVER_PATH,
+        SCREENSAVER_INTERFACE,
+    )
+    .await?;
+
+    // Ask the screensaver if it is currently active
+    Ok(screensaver.call("GetActive", &()).await?)
+}
+
+pub fn parse_lock_signal(signal: &Message) -> anyhow::Result<bool> {
+    // The ActiveChanged signal carries a single boolean for the new state
+    Ok(signal.body().deserialize::<bool>()?)
+}
@@ -0,0 +1,138 @@
+use directories::ProjectDirs;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::utils::local_now;
+use time::macros::format_description;
+
+// How many days of totals are kept in the statistics file
+const STATS_KEEP_DAYS: usize = 366;
+
+// The time the screen was kept awake, for this session and for today
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StatsTotals {
+    /// How long the screen has been kept awake since the application started
+    pub session: Duration,
+
+    /// How long the screen has been kept awake today, across sessions
+    pub today: Duration,
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    /// When the screen started being kept awake (None if it is not being kept awake)
+    blocked_since: Option<Instant>,
+
+    /// The time kept awake in the blocks of this session that have ended
+    session: Duration,
+
+    /// The seconds kept awake in the blocks that have ended, keyed by the local date they ended on (YYYY-MM-DD)
+    daily: BTreeMap<String, u64>,
+}
+
+pub struct StatsCollector {
+    /// The totals, and when the current block started
+    stats: Mutex<Stats>,
+
+    /// The file the daily totals are kept in (None if there is no data directory)
+    path: Option<PathBuf>,
+}
+
+impl StatsCollector {
+    pub fn load() -> Self {
+        // Keep the totals next to the logs
+        let path = ProjectDirs::from("com", "MediaBlocker", "MediaBlocker")
+            .map(|proj_dirs| proj_dirs.data_dir().join("stats.json"));
+
+        // Continue from the daily totals of previous sessions, a missing or unreadable file starts afresh
+        let daily = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Self {
+            stats: Mutex::new(Stats { daily, ..Stats::default() }),
+            path,
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn in_memory() -> Self {
+        // Keep the totals without a file, so tests do not touch the user's statistics
+        Self {
+            stats: Mutex::new(Stats::default()),
+            path: None,
+        }
+    }
+
+    pub fn record(&self, blocked: bool) {
+        let mut stats = self.stats.lock().unwrap();
+
+        // Start timing when the screen starts being kept awake (ignoring repeats of the same transition)
+        if blocked {
+            stats.blocked_since.get_or_insert_with(Instant::now);
+            return;
+        }
+
+        // When it stops, add the time it was kept awake to the totals (a block over midnight counts for the day it ended)
+        let Some(since) = stats.blocked_since.take() else {
+            return;
+        };
+        let elapsed = since.elapsed();
+        stats.session += elapsed;
+        *stats.daily.entry(today()).or_default() += elapsed.as_secs();
+
+        // Drop the oldest days, the dates sort in the order they happened
+        while stats.daily.len() > STATS_KEEP_DAYS {
+            stats.daily.pop_first();
+        }
+
+        // Keep the daily totals across sessions
+        if let Err(e) = self.save(&stats.daily) {
+            log::warn!("[STATS] Failed to save the statistics: {}", e);
+        }
+    }
+
+    pub fn totals(&self) -> StatsTotals {
+        let stats = self.stats.lock().unwrap();
+
+        // Include the block that is still going on
+        let ongoing = stats.blocked_since.map(|since| since.elapsed()).unwrap_or_default();
+        let today = stats.daily.get(&today()).copied().unwrap_or_default();
+        StatsTotals {
+            session: stats.session + ongoing,
+            today: Duration::from_secs(today) + ongoing,
+        }
+    }
+
+    fn save(&self, daily: &BTreeMap<String, u64>) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        // Recursively create the data directory and any parents
+        if let Some(data_dir) = path.parent() {
+            std::fs::create_dir_all(data_dir)?;
+        }
+
+        // Write the daily totals as JSON
+        std::fs::write(path, serde_json::to_string_pretty(daily)?)?;
+        Ok(())
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    // Show hours and minutes, which is as precise as the totals are useful
+    let minutes = duration.as_secs() / 60;
+    match minutes / 60 {
+        0 => format!("{}m", minutes),
+        hours => format!("{}h {}m", hours, minutes % 60),
+    }
+}
+
+fn today() -> String {
+    // Use the local date, as that is the day the user thinks in
+    local_now().format(format_description!("[year]-[month]-[day]")).unwrap_or_default()
+}
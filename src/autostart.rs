@@ -0,0 +1,60 @@
+use anyhow::{Context, Result};
+use directories::BaseDirs;
+use std::path::PathBuf;
+
+pub fn install_autostart() -> Result<()> {
+    // Launch this binary on login, wherever it was installed
+    let exe = std::env::current_exe().context("Failed to find the path of MediaBlocker")?;
+    let entry = format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=MediaBlocker\n\
+         Comment=Keep the screen awake while media is playing\n\
+         Exec=\"{}\"\n\
+         Terminal=false\n\
+         X-GNOME-Autostart-enabled=true\n",
+        exe.display()
+    );
+
+    // Leave an entry that is already up to date alone, and replace one that points elsewhere
+    let path = autostart_path()?;
+    let existing = std::fs::read_to_string(&path).ok();
+    if existing.as_deref() == Some(entry.as_str()) {
+        println!("Autostart is already installed at {}", path.display());
+        return Ok(());
+    }
+
+    // Recursively create the autostart directory and any parents
+    if let Some(autostart_dir) = path.parent() {
+        std::fs::create_dir_all(autostart_dir)?;
+    }
+
+    // Write the entry
+    std::fs::write(&path, entry).with_context(|| format!("Failed to write {}", path.display()))?;
+    match existing {
+        Some(_) => println!("Updated the autostart entry at {} to launch {}", path.display(), exe.display()),
+        None => println!("Installed the autostart entry at {} to launch {}", path.display(), exe.display()),
+    }
+    Ok(())
+}
+
+pub fn uninstall_autostart() -> Result<()> {
+    // Remove the entry, which is already done if there is none
+    let path = autostart_path()?;
+    match std::fs::remove_file(&path) {
+        Ok(()) => println!("Removed the autostart entry at {}", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!("Autostart is not installed, there is no {}", path.display())
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to remove {}", path.display())),
+    }
+    Ok(())
+}
+
+fn autostart_path() -> Result<PathBuf> {
+    // Match on the state for the parsing of the user's directories ($XDG_CONFIG_HOME or ~/.config)
+    match BaseDirs::new() {
+        Some(base_dirs) => Ok(base_dirs.config_dir().join("autostart").join("mediablocker.desktop")),
+        None => Err(anyhow::anyhow!("Failed to detect the config directory")),
+    }
+}
@@ -0,0 +1,88 @@
+// The usage printed by --help
+pub const USAGE: &str = "\
+Usage: MediaBlocker [OPTIONS]
+
+Keeps the screen awake while media is playing, from the system tray.
+
+Options:
+      --log-level <LEVEL>  Log at this level (off, error, warn, info, debug or trace)
+      --log-format <FORMAT>
+                           Write the log file as text or json
+      --status             Print the state of the blocker and exit
+      --json               Print the status as JSON (with --status)
+      --doctor             Print what the environment supports, for bug reports, and exit
+      --test-inhibit       Keep the screen awake for 5 seconds to check it works, and exit
+      --install-autostart  Launch MediaBlocker on login, and exit
+      --uninstall-autostart
+                           Stop launching MediaBlocker on login, and exit
+  -h, --help               Print this help and exit
+  -V, --version            Print the version and exit";
+
+// The options passed to the application on the command line
+#[derive(Clone, Debug, Default)]
+pub struct Cli {
+    /// The level to log at, overriding the environment and the config (None if not given)
+    pub log_level: Option<String>,
+
+    /// How the log file is written, overriding the config (None if not given)
+    pub log_format: Option<String>,
+
+    /// Print the state of the blocker and exit, rather than starting the tray
+    pub status: bool,
+
+    /// Print the status as JSON rather than as a line of text
+    pub json: bool,
+
+    /// Take and release an inhibit to check the backend works, rather than starting the tray
+    pub test_inhibit: bool,
+
+    /// Print the capabilities of the environment for a bug report, rather than starting the tray
+    pub doctor: bool,
+
+    /// Write the autostart entry that launches the application on login, then exit
+    pub install_autostart: bool,
+
+    /// Remove the autostart entry, then exit
+    pub uninstall_autostart: bool,
+
+    /// Print the usage and exit
+    pub help: bool,
+
+    /// Print the version and exit
+    pub version: bool,
+
+    /// Arguments that were not recognised, reported once logging has been set up
+    pub unrecognised: Vec<String>,
+}
+
+impl Cli {
+    pub fn parse() -> Self {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    fn parse_from(args: impl IntoIterator<Item = String>) -> Self {
+        let mut cli = Cli::default();
+        let mut args = args.into_iter();
+
+        // Walk the arguments, accepting both "--flag value" and "--flag=value"
+        while let Some(arg) = args.next() {
+            match arg.split_once('=') {
+                Some(("--log-level", level)) => cli.log_level = Some(level.to_string()),
+                None if arg == "--log-level" => cli.log_level = args.next(),
+                Some(("--log-format", format)) => cli.log_format = Some(format.to_string()),
+                None if arg == "--log-format" => cli.log_format = args.next(),
+                None if arg == "--status" => cli.status = true,
+                None if arg == "--json" => cli.json = true,
+                None if arg == "--test-inhibit" => cli.test_inhibit = true,
+                None if arg == "--doctor" => cli.doctor = true,
+                None if arg == "--install-autostart" => cli.install_autostart = true,
+                None if arg == "--uninstall-autostart" => cli.uninstall_autostart = true,
+                None if arg == "--help" || arg == "-h" => cli.help = true,
+                None if arg == "--version" || arg == "-V" => cli.version = true,
+                _ => cli.unrecognised.push(arg),
+            }
+        }
+
+        cli
+    }
+}
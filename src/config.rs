@@ -0,0 +1,240 @@
+use crate::global_constants::{INHIBIT_APP_NAME, INHIBIT_REASON};
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// The user's settings, read from config.toml in the config directory
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// Indicate if the blocker is enabled when the application starts
+    pub enabled_on_start: bool,
+
+    /// The application name shown by the desktop for our inhibits
+    pub inhibit_app_name: String,
+
+    /// The reason shown by the desktop for our inhibits
+    pub inhibit_reason: String,
+
+    /// The level to log at (off, error, warn, info, debug or trace)
+    pub log_level: String,
+
+    /// How the log file is written: "text" (the default) or "json" for a JSON object per line
+    pub log_format: String,
+
+    /// How the log file is named: "stable" (the default) for one file, "pid" or "timestamp" for a file per run
+    pub log_naming: String,
+
+    /// Log at the debug level or above regardless of the log level, toggled by the tray's "Verbose Logging"
+    pub verbose_logging: bool,
+
+    /// The size in MB the log file can grow to before it is rotated (0 to never rotate)
+    pub log_max_size_mb: u64,
+
+    /// The number of rotated log files to keep
+    pub log_keep_count: usize,
+
+    /// How the screen is kept awake: "inhibit" (the default) or "simulate" to report user activity on a timer
+    pub keep_awake_mode: String,
+
+    /// Also stop the machine from suspending while the screen is kept awake, through a logind sleep inhibitor
+    pub inhibit_suspend: bool,
+
+    /// Only these media players can keep the screen awake, matched on the end of their bus name (e.g. "spotify")
+    pub allowlist: Vec<String>,
+
+    /// These media players are ignored, unless an allowlist is set (which takes priority)
+    pub denylist: Vec<String>,
+
+    /// Only keep the screen awake for video, ignoring players that are playing audio
+    pub video_only: bool,
+
+    /// Ignore media played from a URL with one of these schemes, e.g. "file" to ignore local files
+    pub ignored_url_schemes: Vec<String>,
+
+    /// The most media players that are tracked, keeping allowlisted players first (0 for no limit)
+    pub max_players: usize,
+
+    /// Keep the screen awake while media is paused, not only while it is playing (stopped media never counts)
+    pub keep_awake_while_paused: bool,
+
+    /// Only keep the screen awake while a sound is actually being output, checked through PulseAudio/PipeWire
+    pub require_active_audio: bool,
+
+    /// How long in seconds a player must keep playing before it keeps the screen awake (0 to block straight away)
+    pub min_play_seconds: u64,
+
+    /// How long in seconds media must stay stopped before the screen is no longer kept awake (0 to release straight away)
+    pub unblock_grace_seconds: u64,
+
+    /// How long in milliseconds a player can pause for (e.g. ducked by a call) and keep its inhibit (0 to disable)
+    pub ducking_threshold_ms: u64,
+
+    /// How long in milliseconds to gather bursts of player signals into one update (0 to update on every signal)
+    pub signal_debounce_ms: u64,
+
+    /// How long in milliseconds to wait for a reply to a D-Bus call before treating it as failed
+    pub dbus_timeout_ms: u64,
+
+    /// How often in seconds to re-check the players, in case a signal was missed (0 to never re-check)
+    pub poll_interval_secs: u64,
+
+    /// The daily windows the screen can be kept awake in, e.g. "18:00-23:30" or "22:00-02:00" (always if empty)
+    pub schedule: Vec<String>,
+
+    /// Stop keeping the screen awake while on battery below this charge in percent (0 to never stop)
+    pub disable_below_battery_percent: u8,
+
+    /// Send desktop notifications for the categories in notify_categories
+    pub notifications: bool,
+
+    /// The notifications that are sent while notifications is on: "block", "unblock" and/or "error"
+    pub notify_categories: Vec<String>,
+
+    /// The urgency the notifications are sent with: "low" (the default), "normal" or "critical"
+    pub notify_urgency: String,
+
+    /// How long in milliseconds the notifications are shown for (-1 for the server's default, 0 until dismissed)
+    pub notify_timeout_ms: i32,
+
+    /// The colour to recolour monochrome tray icons to ("#RRGGBB", "auto" to match the theme, or "" to use them as-is)
+    pub icon_tint: String,
+
+    /// Pulse the tray icon while the screen is kept awake, so the state is more noticeable
+    pub animate_active: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled_on_start: true,
+            inhibit_app_name: INHIBIT_APP_NAME.to_string(),
+            inhibit_reason: INHIBIT_REASON.to_string(),
+            log_level: "warn".to_string(),
+            log_format: "text".to_string(),
+            log_naming: "stable".to_string(),
+            verbose_logging: false,
+            log_max_size_mb: 5,
+            log_keep_count: 3,
+            keep_awake_mode: "inhibit".to_string(),
+            inhibit_suspend: false,
+            allowlist: Vec::new(),
+            denylist: Vec::new(),
+            video_only: false,
+            ignored_url_schemes: Vec::new(),
+            max_players: 0,
+            keep_awake_while_paused: false,
+            require_active_audio: false,
+            min_play_seconds: 0,
+            unblock_grace_seconds: 0,
+            ducking_threshold_ms: 0,
+            signal_debounce_ms: 250,
+            dbus_timeout_ms: 3000,
+            poll_interval_secs: 30,
+            schedule: Vec::new(),
+            disable_below_battery_percent: 0,
+            notifications: false,
+            notify_categories: vec!["block".to_string(), "unblock".to_string()],
+            notify_urgency: "low".to_string(),
+            notify_timeout_ms: -1,
+            icon_tint: String::new(),
+            animate_active: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        // Get the config file
+        let config_file = config_path()?;
+
+        // Write the defaults on the first run, so the user has a file to edit
+        if !config_file.exists() {
+            let config = Config::default();
+            config.save()?;
+            return Ok(config);
+        }
+
+        // Read and parse the config file, any field that is missing takes its default
+        let contents = std::fs::read_to_string(&config_file)
+            .with_context(|| format!("Failed to read {}", config_file.display()))?;
+        let config: Config = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", config_file.display()))?;
+        Ok(config.with_defaults_for_blanks())
+    }
+
+    fn with_defaults_for_blanks(mut self) -> Self {
+        // A blank name or reason would show up as an unlabelled entry in the desktop's inhibit list
+        if self.inhibit_app_name.trim().is_empty() {
+            self.inhibit_app_name = INHIBIT_APP_NAME.to_string();
+        }
+        if self.inhibit_reason.trim().is_empty() {
+            self.inhibit_reason = INHIBIT_REASON.to_string();
+        }
+        self
+    }
+
+    pub fn verbose_level(&self, level: LevelFilter) -> LevelFilter {
+        // Verbose logging raises the level to debug, keeping a more detailed level (trace) as it is
+        if self.verbose_logging { level.max(LevelFilter::Debug) } else { level }
+    }
+
+    pub fn update(change: impl FnOnce(&mut Config)) -> Result<()> {
+        // Apply the change to the settings on disk, so edits made since startup are kept
+        let mut config = Config::load()?;
+        change(&mut config);
+        config.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        // Get the config file
+        let config_file = config_path()?;
+
+        // Recursively create the config directory and any parents
+        if let Some(config_dir) = config_file.parent() {
+            std::fs::create_dir_all(config_dir)?;
+        }
+
+        // Write the config as TOML
+        let contents = toml::to_string_pretty(self)?;
+        std::fs::write(&config_file, contents)
+            .with_context(|| format!("Failed to write {}", config_file.display()))
+    }
+}
+
+pub fn config_path() -> Result<PathBuf> {
+    // Match on the state for the parsing of the project directory
+    match ProjectDirs::from("com", "MediaBlocker", "MediaBlocker") {
+        Some(proj_dirs) => Ok(proj_dirs.config_dir().join("config.toml")),
+        None => Err(anyhow::anyhow!("Failed to detect project directory")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_toml(config: &Config) -> String {
+        toml::to_string(config).unwrap()
+    }
+
+    #[test]
+    fn missing_settings_take_their_defaults() {
+        let config: Config = toml::from_str("max_players = 4").unwrap();
+        assert_eq!(config.max_players, 4);
+        assert_eq!(to_toml(&Config { max_players: 0, ..config }), to_toml(&Config::default()));
+    }
+
+    #[test]
+    fn readme_example_matches_the_defaults() {
+        // The README documents every setting with its default, so it must be kept in step with Config
+        let readme = include_str!("../README.md");
+        let start = readme.find("```toml\n").unwrap() + "```toml\n".len();
+        let end = start + readme[start..].find("```").unwrap();
+        let example: toml::Table = toml::from_str(&readme[start..end]).unwrap();
+        let defaults: toml::Table = toml::from_str(&to_toml(&Config::default())).unwrap();
+        assert_eq!(example, defaults);
+    }
+}
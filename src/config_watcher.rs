@@ -0,0 +1,73 @@
+use crate::app::application::Application;
+use crate::config::{config_path, Config};
+use crate::global_constants::CONFIG_RELOAD_DEBOUNCE;
+use anyhow::{Context, Result};
+use log::LevelFilter;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::str::FromStr;
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+pub fn watch_config(app: Arc<Application>, pinned_level: Option<LevelFilter>) -> Result<RecommendedWatcher> {
+    // Get the config file and the directory it is in
+    let config_file = config_path()?;
+    let config_dir = config_file.parent().context("The config file has no parent directory")?.to_path_buf();
+    let file_name = config_file.file_name().map(|name| name.to_os_string());
+
+    // Forward the changes made to the config file, ignoring the other files in the directory
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+        Ok(event) => {
+            let is_change = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_));
+            if is_change && event.paths.iter().any(|path| path.file_name() == file_name.as_deref()) {
+                let _ = sender.send(());
+            }
+        }
+        Err(e) => log::warn!("[CONFIG] Failed to watch the config file: {}", e),
+    })?;
+
+    // Watch the directory rather than the file, as editors often save by replacing the file
+    watcher.watch(&config_dir, RecursiveMode::NonRecursive)?;
+    log::info!("[CONFIG] Watching {} for changes", config_file.display());
+
+    thread::spawn(move || {
+        while receiver.recv().is_ok() {
+            // Wait for the changes to settle, as editors often write the file more than once per save
+            while receiver.recv_timeout(CONFIG_RELOAD_DEBOUNCE).is_ok() {}
+
+            // The file is missing mid-save, and loading it now would overwrite the user's config with the defaults
+            if !config_file.exists() {
+                log::debug!("[CONFIG] The config file was removed, waiting for it to be written");
+                continue;
+            }
+            reload_config(&app, pinned_level);
+        }
+    });
+
+    // Return the watcher, which stops watching once dropped
+    Ok(watcher)
+}
+
+pub fn reload_config(app: &Application, pinned_level: Option<LevelFilter>) {
+    // Keep the previous settings if the file cannot be read or parsed (e.g. it is being edited)
+    let config = match app.reload_config() {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("[CONFIG] Failed to reload the config, keeping the previous settings: {:#}", e);
+            return;
+        }
+    };
+
+    apply_log_level(&config, pinned_level);
+}
+
+pub fn apply_log_level(config: &Config, pinned_level: Option<LevelFilter>) {
+    // The log level from the command line or environment takes priority over the config's
+    let level = pinned_level.unwrap_or_else(|| {
+        LevelFilter::from_str(config.log_level.trim()).unwrap_or_else(|_| {
+            log::warn!("[CONFIG] Unknown log level: {}, using warn", config.log_level);
+            LevelFilter::Warn
+        })
+    });
+    log::set_max_level(config.verbose_level(level));
+}
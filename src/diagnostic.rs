@@ -0,0 +1,133 @@
+use crate::app::backends::{choose_backend, list_bus_names, select_backend, BackendKind};
+use crate::app::media_player::{list_players, PlayerFilter};
+use crate::config::{config_path, Config};
+use crate::exit_status::ExitStatus;
+use crate::global_constants::TEST_INHIBIT_DURATION;
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use std::time::Duration;
+use zbus::{connection, Connection};
+
+pub async fn doctor(config: &Config) -> Result<()> {
+    println!("MediaBlocker {}", env!("CARGO_PKG_VERSION"));
+
+    // Report where the settings and logs are read from and written to
+    match config_path() {
+        Ok(path) => println!("Config file: {}", path.display()),
+        Err(e) => println!("Config file: unknown ({})", e),
+    }
+    match ProjectDirs::from("com", "MediaBlocker", "MediaBlocker") {
+        Some(proj_dirs) => println!("Log directory: {}", proj_dirs.data_dir().display()),
+        None => println!("Log directory: unknown (failed to detect project directory)"),
+    }
+
+    // Check that both buses are reachable, nothing else can be checked without the D-Bus session
+    let dbus_timeout = Duration::from_millis(config.dbus_timeout_ms);
+    let conn = match connection::Builder::session()?.method_timeout(dbus_timeout).build().await {
+        Ok(conn) => {
+            println!("D-Bus session: reachable");
+            conn
+        }
+        Err(e) => {
+            println!("D-Bus session: unreachable ({})", e);
+            return Err(e).context(ExitStatus::BusConnectionFailed);
+        }
+    };
+    let system_conn = match connection::Builder::system()?.method_timeout(dbus_timeout).build().await {
+        Ok(system_conn) => {
+            println!("D-Bus system bus: reachable");
+            Some(system_conn)
+        }
+        Err(e) => {
+            println!("D-Bus system bus: unreachable ({})", e);
+            None
+        }
+    };
+
+    // Report which inhibit services are registered, and which of them the tray would pick
+    let session_names = bus_names(&conn).await;
+    let system_names = match &system_conn {
+        Some(system_conn) => bus_names(system_conn).await,
+        None => Vec::new(),
+    };
+    println!("Inhibit services:");
+    for kind in BackendKind::ALL {
+        let names = if kind.is_on_system_bus() { &system_names } else { &session_names };
+        let registered = names.iter().any(|name| name == kind.bus_name());
+        println!("  {}: {}", kind.bus_name(), if registered { "registered" } else { "not registered" });
+    }
+    match choose_backend(&session_names, &system_names) {
+        Some(kind) => println!("Detected backend: {:?}", kind),
+        None => println!("Detected backend: none, the screen cannot be kept awake"),
+    }
+    if let Ok(requested) = std::env::var("MEDIABLOCKER_BACKEND") {
+        println!("Requested backend (MEDIABLOCKER_BACKEND): {}", requested);
+    }
+
+    // Report every media player on the D-Bus session, and whether the user's filter tracks it
+    let filter = PlayerFilter::from_config(config);
+    match list_players(&conn, &filter).await {
+        Ok(players) if players.is_empty() => println!("Media players: none"),
+        Ok(players) => {
+            println!("Media players:");
+            for player in players {
+                let tracked = if player.tracked { "tracked" } else { "ignored" };
+                println!("  {} ({:?}, {})", player.name, player.status, tracked);
+            }
+        }
+        Err(e) => println!("Media players: unable to list ({})", e),
+    }
+
+    Ok(())
+}
+
+async fn bus_names(conn: &Connection) -> Vec<String> {
+    // A bus that cannot be listed is reported as having no services, rather than ending the report
+    list_bus_names(conn).await.unwrap_or_else(|e| {
+        println!("Unable to list the names on the bus: {}", e);
+        Vec::new()
+    })
+}
+
+pub async fn test_inhibit(config: &Config) -> Result<()> {
+    // Connect the same way as the tray, so the test goes through the same timeouts
+    let dbus_timeout = Duration::from_millis(config.dbus_timeout_ms);
+    let conn = connection::Builder::session()?
+        .method_timeout(dbus_timeout)
+        .build()
+        .await
+        .context(ExitStatus::BusConnectionFailed)?;
+    let system_conn = match connection::Builder::system()?.method_timeout(dbus_timeout).build().await {
+        Ok(system_conn) => Some(system_conn),
+        Err(e) => {
+            println!("The D-Bus system bus is unavailable ({}), the logind backend cannot be used", e);
+            None
+        }
+    };
+
+    // Select the backend the tray would use (honouring MEDIABLOCKER_BACKEND)
+    let Some(backend) = select_backend(&conn, system_conn.as_ref(), &config.keep_awake_mode).await else {
+        return Err(anyhow::anyhow!("the screen cannot be kept awake on this desktop")).context(ExitStatus::NoBackend);
+    };
+    println!("Using the {} inhibit backend", backend.name());
+
+    // Take an inhibit, as the tray does when media starts playing
+    let reason = format!("{} (Test Inhibit)", config.inhibit_reason);
+    let handle = backend
+        .inhibit(&conn, &config.inhibit_app_name, &reason)
+        .await
+        .with_context(|| format!("Inhibit failed on the {} backend", backend.name()))?;
+    println!("Inhibit succeeded, holding it for {} seconds", TEST_INHIBIT_DURATION.as_secs());
+
+    // Hold the inhibit long enough to be seen in the desktop's list of inhibitors
+    async_std::task::sleep(TEST_INHIBIT_DURATION).await;
+
+    // Release the inhibit, as the tray does when media stops
+    backend
+        .uninhibit(&conn, handle)
+        .await
+        .with_context(|| format!("UnInhibit failed on the {} backend", backend.name()))?;
+    println!("UnInhibit succeeded, the {} backend is working", backend.name());
+
+    Ok(())
+}
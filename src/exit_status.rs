@@ -0,0 +1,84 @@
+use std::fmt;
+use std::process::ExitCode;
+
+// Failure categories that map to documented process exit codes (see the README)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// Any failure that does not belong to a more specific category
+    Failure,
+
+    /// GTK could not be initialised, so there is nowhere to show the tray
+    GtkInitFailed,
+
+    /// The log file could not be set up
+    LoggingFailed,
+
+    /// The connection to the D-Bus session bus could not be established
+    BusConnectionFailed,
+
+    /// The system tray menu or icon could not be created
+    TrayFailed,
+
+    /// A monitor crashed while exiting on failure was requested
+    MonitorCrashed,
+
+    /// The config file could not be read, parsed or created
+    ConfigFailed,
+
+    /// Another instance of the application is already running
+    AlreadyRunning,
+
+    /// No inhibit backend is available, so the screen cannot be kept awake
+    NoBackend,
+}
+
+impl ExitStatus {
+    pub fn code(&self) -> u8 {
+        match self {
+            ExitStatus::Failure => 1,
+            ExitStatus::GtkInitFailed => 2,
+            ExitStatus::LoggingFailed => 3,
+            ExitStatus::BusConnectionFailed => 4,
+            ExitStatus::TrayFailed => 5,
+            ExitStatus::MonitorCrashed => 6,
+            ExitStatus::ConfigFailed => 7,
+            ExitStatus::AlreadyRunning => 8,
+            ExitStatus::NoBackend => 9,
+        }
+    }
+
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        // Errors tagged with a category via `.context(...)` exit with that category's code
+        error
+            .downcast_ref::<ExitStatus>()
+            .copied()
+            .unwrap_or(ExitStatus::Failure)
+    }
+
+    pub fn exit(self) -> ! {
+        std::process::exit(self.code().into())
+    }
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            ExitStatus::Failure => "MediaBlocker failed",
+            ExitStatus::GtkInitFailed => "Failed to initialize GTK",
+            ExitStatus::LoggingFailed => "Failed to set up logging",
+            ExitStatus::BusConnectionFailed => "Failed to connect to the D-Bus session bus",
+            ExitStatus::TrayFailed => "Failed to create the system tray",
+            ExitStatus::MonitorCrashed => "A monitor has crashed",
+            ExitStatus::ConfigFailed => "Failed to load the config file",
+            ExitStatus::AlreadyRunning => "MediaBlocker is already running",
+            ExitStatus::NoBackend => "No inhibit backend is available",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        ExitCode::from(status.code())
+    }
+}
@@ -1,22 +1,94 @@
 use async_std::stream::Stream;
-use futures::stream::SelectAll;
+use std::time::Duration;
 
 // Paths to DBus object
 pub const DBUS_DESTINATION: &str = "org.freedesktop.DBus";
 pub const DBUS_PATH: &str = "/org/freedesktop/DBus";
 pub const DBUS_INTERFACE: &str = "org.freedesktop.DBus";
+pub const DBUS_PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
 
 // Paths to the MediaPlayer object
 pub const MEDIA_PLAYER_PATH: &str = "/org/mpris/MediaPlayer2";
 pub const MEDIA_PLAYER_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+pub const MEDIA_PLAYER_CONTROL_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+pub const MEDIA_PLAYER_ROOT_INTERFACE: &str = "org.mpris.MediaPlayer2";
 
 // Paths to the Idle Inhibition Service (ScreenSaver)
 pub const SCREENSAVER_DESTINATION: &str = "org.freedesktop.ScreenSaver";
 pub const SCREENSAVER_PATH: &str = "/org/freedesktop/ScreenSaver";
 pub const SCREENSAVER_INTERFACE: &str = "org.freedesktop.ScreenSaver";
 
+// Paths to GNOME's session manager, which replaces the ScreenSaver service on GNOME
+pub const GNOME_SESSION_MANAGER_DESTINATION: &str = "org.gnome.SessionManager";
+pub const GNOME_SESSION_MANAGER_PATH: &str = "/org/gnome/SessionManager";
+pub const GNOME_SESSION_MANAGER_INTERFACE: &str = "org.gnome.SessionManager";
+
+// GNOME inhibit flags to set (Idle), preventing the session from being marked as idle
+pub const GNOME_INHIBIT_IDLE: u32 = 8;
+
+// How long a computed "is any media playing" result is reused before querying the players again
+pub const MEDIA_STATUS_CACHE_TTL: Duration = Duration::from_millis(250);
+
+// How long to wait before restarting a crashed monitor, doubling on each crash up to the maximum
+pub const MONITOR_RESTART_MIN_BACKOFF: Duration = Duration::from_secs(1);
+pub const MONITOR_RESTART_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// How long to wait before trying again to take an inhibit that failed (e.g. the call timed out)
+pub const INHIBIT_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+// How often to look for an inhibit backend again, while none is available
+pub const BACKEND_RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+// How long the --test-inhibit diagnostic holds its inhibit before releasing it
+pub const TEST_INHIBIT_DURATION: Duration = Duration::from_secs(5);
+
+// How often user activity is reported while the screen is kept awake in the simulate mode
+pub const SIMULATE_ACTIVITY_INTERVAL: Duration = Duration::from_secs(30);
+
+// How long a block/unblock notification waits for the state to settle, so rapid flips send one notification
+pub const NOTIFICATION_COALESCE_WINDOW: Duration = Duration::from_secs(2);
+
+// How long the config file must go unchanged before it is reloaded, as editors often write it more than once per save
+pub const CONFIG_RELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
+
+// How many refreshes the tray and UI channels hold, further refreshes are dropped as one is already queued
+pub const REFRESH_CHANNEL_CAPACITY: usize = 4;
+
+// Where the project's source and issue tracker are hosted, shown in the About dialog
+pub const REPOSITORY_URL: &str = "https://github.com/Ethan-Dankiw/MediaBlocker";
+
+// The application name and reason shown by the desktop for our inhibits
+pub const INHIBIT_APP_NAME: &str = "Rust Media Monitor";
+pub const INHIBIT_REASON: &str = "Media is currently playing";
+
+// Paths to KDE's PowerManagement policy agent, which also prevents the session from sleeping
+pub const KDE_POWER_MANAGEMENT_DESTINATION: &str = "org.kde.Solid.PowerManagement";
+pub const KDE_POWER_MANAGEMENT_PATH: &str = "/org/kde/Solid/PowerManagement/PolicyAgent";
+pub const KDE_POWER_MANAGEMENT_INTERFACE: &str = "org.kde.Solid.PowerManagement.PolicyAgent";
+
+// KDE policies to inhibit (InterruptSession | ChangeScreenSettings), preventing both sleep and screen dimming
+pub const KDE_INHIBIT_POLICIES: u32 = 1 | 4;
+
+// Paths to the desktop notification server
+pub const NOTIFICATIONS_DESTINATION: &str = "org.freedesktop.Notifications";
+pub const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+pub const NOTIFICATIONS_INTERFACE: &str = "org.freedesktop.Notifications";
+
+// The name and path MediaBlocker's own D-Bus interface is served at
+pub const MANAGER_NAME: &str = "org.mediablocker.Manager";
+pub const MANAGER_PATH: &str = "/org/mediablocker/Manager";
+
+// Paths to the systemd-logind manager (on the system bus)
+pub const LOGIN1_DESTINATION: &str = "org.freedesktop.login1";
+pub const LOGIN1_PATH: &str = "/org/freedesktop/login1";
+pub const LOGIN1_INTERFACE: &str = "org.freedesktop.login1.Manager";
+
+// Paths to the UPower daemon and its combined battery device (on the system bus)
+pub const UPOWER_DESTINATION: &str = "org.freedesktop.UPower";
+pub const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+pub const UPOWER_INTERFACE: &str = "org.freedesktop.UPower";
+pub const UPOWER_DISPLAY_DEVICE_PATH: &str = "/org/freedesktop/UPower/devices/DisplayDevice";
+pub const UPOWER_DEVICE_INTERFACE: &str = "org.freedesktop.UPower.Device";
+
 // Type alias for the stream of D-Bus messages
 pub type DbusSignalStream = std::pin::Pin<Box<dyn Stream<Item = zbus::Message> + Send>>;
-
-// Type alias for a set of all streams
-pub type UnifiedStream = SelectAll<DbusSignalStream>;
@@ -0,0 +1,83 @@
+use crate::exit_status::ExitStatus;
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+pub struct InstanceLock {
+    /// The lock file, locked for as long as this instance is running
+    file: File,
+}
+
+impl InstanceLock {
+    pub fn acquire() -> Result<Self> {
+        // Get the lock file
+        let path = lock_path()?;
+
+        // Recursively create the lock directory and any parents
+        if let Some(lock_dir) = path.parent() {
+            std::fs::create_dir_all(lock_dir)?;
+        }
+
+        // Open the lock file without truncating it, as another instance may have written its PID
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        // The lock is held by the kernel, so a crashed instance never leaves it locked
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                // Report the PID of the running instance, so the user can find it
+                let mut pid = String::new();
+                let _ = file.read_to_string(&mut pid);
+                return Err(anyhow::anyhow!("{} is locked by PID {}", path.display(), pid.trim()))
+                    .context(ExitStatus::AlreadyRunning);
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Failed to lock {}", path.display()));
+            }
+        }
+
+        // Record the PID of this instance
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+
+        Ok(Self { file })
+    }
+
+    pub fn is_held() -> bool {
+        // Without a lock file, no instance has started
+        let Ok(path) = lock_path() else {
+            return false;
+        };
+        let Ok(file) = File::open(&path) else {
+            return false;
+        };
+
+        // The lock is only held while an instance is running (closing the file releases our attempt)
+        matches!(file.try_lock_shared(), Err(TryLockError::WouldBlock))
+    }
+
+    pub fn release(&self) {
+        // Leave the lock file in place, as removing it lets a new instance lock a different file to one still locked
+        let _ = self.file.unlock();
+    }
+}
+
+fn lock_path() -> Result<PathBuf> {
+    // Match on the state for the parsing of the project directory
+    match ProjectDirs::from("com", "MediaBlocker", "MediaBlocker") {
+        // Prefer the runtime directory, which is cleared on logout, falling back to the cache
+        Some(proj_dirs) => {
+            let lock_dir = proj_dirs.runtime_dir().unwrap_or(proj_dirs.cache_dir());
+            Ok(lock_dir.join("media_blocker.lock"))
+        }
+        None => Err(anyhow::anyhow!("Failed to detect project directory")),
+    }
+}
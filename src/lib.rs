@@ -0,0 +1,16 @@
+// The monitoring and inhibit logic, shared by the binary and anything else that wants to reuse it
+pub mod app;
+pub mod autostart;
+pub mod cli;
+pub mod config;
+pub mod config_watcher;
+pub mod diagnostic;
+pub mod exit_status;
+pub mod global_constants;
+pub mod instance;
+pub mod logging;
+pub mod status;
+pub mod utils;
+pub mod ui;
+#[cfg(test)]
+mod test_support;
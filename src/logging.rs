@@ -0,0 +1,241 @@
+use crate::utils::local_now;
+use anyhow::Result;
+use directories::ProjectDirs;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use simplelog::{ColorChoice, Config, SharedLogger, TermLogger, TerminalMode, WriteLogger};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+
+// Scheme used to name the log file, so concurrent or successive runs do not clobber each other's logs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogNaming {
+    /// A single stable file that every run appends to
+    Stable,
+
+    /// A file per process, named with the PID of the process
+    Pid,
+
+    /// A file per run, named with the time the run started
+    Timestamp,
+}
+
+impl LogNaming {
+    pub fn parse(naming: &str) -> Option<Self> {
+        match naming.trim().to_lowercase().as_str() {
+            "stable" => Some(LogNaming::Stable),
+            "pid" => Some(LogNaming::Pid),
+            "timestamp" => Some(LogNaming::Timestamp),
+            _ => None,
+        }
+    }
+
+    fn file_name(&self) -> String {
+        match self {
+            LogNaming::Stable => "media_blocker.log".to_string(),
+            LogNaming::Pid => format!("media_blocker-{}.log", std::process::id()),
+            LogNaming::Timestamp => {
+                let format = format_description!("[year]-[month]-[day]_[hour]-[minute]-[second]");
+                let started = local_now().format(format).unwrap_or_default();
+                format!("media_blocker-{}.log", started)
+            }
+        }
+    }
+}
+
+// How each record is written to the log file (the terminal is always written as text)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// A line of text per record, for reading by people
+    Text,
+
+    /// A JSON object per line, for log collectors
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.trim().to_lowercase().as_str() {
+            "text" => Some(LogFormat::Text),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+// When the log file is rotated, so a long running session cannot grow it without bound
+#[derive(Clone, Copy, Debug)]
+pub struct LogRotation {
+    /// The size in bytes the log file can grow to before it is rotated (0 to never rotate)
+    pub max_size: u64,
+
+    /// The number of rotated log files to keep (media_blocker.log.1, .2, ...)
+    pub keep_count: usize,
+}
+
+pub fn setup_logging(naming: LogNaming, format: LogFormat, level: LevelFilter, rotation: LogRotation) -> Result<PathBuf> {
+    // Match on the state for the parsing of the project directory
+    match ProjectDirs::from("com", "MediaBlocker", "MediaBlocker") {
+        Some(proj_dirs) => {
+            // Get the log directory
+            let log_dir = proj_dirs.data_dir();
+
+            // Recursively create the log directory and any parents
+            std::fs::create_dir_all(log_dir)?;
+
+            // Get the log file
+            let log_file = log_dir.join(naming.file_name());
+
+            // Move the log file aside if it has grown too large
+            rotate(&log_file, rotation)?;
+
+            // Open the log file for appending, so the logs of previous runs are kept
+            let mut file = OpenOptions::new().create(true).append(true).open(&log_file)?;
+
+            // Separate the logs of this run from the logs of previous runs
+            let started = format!("MediaBlocker {} started (pid {})", env!("CARGO_PKG_VERSION"), std::process::id());
+            match format {
+                LogFormat::Text => writeln!(file, "===== {} at {} =====", started, local_now())?,
+                LogFormat::Json => writeln!(file, "{}", json_line(Level::Info, "mediablocker", &format!("[SYSTEM] {}", started)))?,
+            }
+
+            // Create the file logger in the requested format
+            let file_logger: Box<dyn SharedLogger> = match format {
+                LogFormat::Text => WriteLogger::new(LevelFilter::Trace, Config::default(), file),
+                LogFormat::Json => Box::new(JsonWriteLogger {
+                    file: Mutex::new(file),
+                    config: Config::default(),
+                }),
+            };
+
+            // Create the logger, letting every level through so the level can be changed by the config at runtime
+            simplelog::CombinedLogger::init(vec![
+                TermLogger::new(
+                    LevelFilter::Trace,
+                    Config::default(),
+                    TerminalMode::Mixed,
+                    ColorChoice::Auto,
+                ),
+                file_logger,
+            ])?;
+
+            // Filter the logs by the requested level
+            log::set_max_level(level);
+
+            // Return the log file
+            Ok(log_file)
+        }
+        None => Err(anyhow::anyhow!("Failed to detect project directory")),
+    }
+}
+
+fn rotate(log_file: &Path, rotation: LogRotation) -> std::io::Result<()> {
+    // Only rotate when enabled and the log file has grown past the maximum size
+    let size = match std::fs::metadata(log_file) {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if rotation.max_size == 0 || size <= rotation.max_size {
+        return Ok(());
+    }
+
+    // Without any rotated files to keep, the log file is simply started fresh
+    if rotation.keep_count == 0 {
+        return std::fs::remove_file(log_file);
+    }
+
+    // Shift each rotated file up by one (.1 -> .2, ...), the oldest is overwritten by the one before it
+    for index in (1..rotation.keep_count).rev() {
+        match std::fs::rename(rotated_path(log_file, index), rotated_path(log_file, index + 1)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+
+    // Move the log file into the first rotated slot
+    std::fs::rename(log_file, rotated_path(log_file, 1))
+}
+
+fn rotated_path(log_file: &Path, index: usize) -> PathBuf {
+    // Append the index to the file name (e.g. media_blocker.log.1)
+    let mut path = OsString::from(log_file.as_os_str());
+    path.push(format!(".{}", index));
+    PathBuf::from(path)
+}
+
+// Writes each record to the log file as a JSON object per line, for session managers that collect the logs
+struct JsonWriteLogger {
+    /// The log file the records are appended to
+    file: Mutex<File>,
+
+    /// The simplelog config, only held as SharedLogger must hand one out
+    config: Config,
+}
+
+impl Log for JsonWriteLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // The level is filtered by log::set_max_level, so it can be changed at runtime
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = json_line(record.level(), record.target(), &record.args().to_string());
+        let _ = writeln!(self.file.lock().unwrap(), "{}", line);
+    }
+
+    fn flush(&self) {
+        let _ = self.file.lock().unwrap().flush();
+    }
+}
+
+impl SharedLogger for JsonWriteLogger {
+    fn level(&self) -> LevelFilter {
+        LevelFilter::Trace
+    }
+
+    fn config(&self) -> Option<&Config> {
+        Some(&self.config)
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        self
+    }
+}
+
+fn json_line(level: Level, target: &str, message: &str) -> String {
+    // Move the "[SECTION]" prefix of the message into its own field, so the logs can be filtered by it
+    let (section, message) = message
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .map_or((None, message), |(section, message)| (Some(section), message.trim_start()));
+
+    serde_json::json!({
+        "timestamp": local_now().format(&Rfc3339).unwrap_or_default(),
+        "level": level.as_str(),
+        "target": target,
+        "section": section,
+        "message": message,
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_naming_is_parsed_from_the_config() {
+        assert_eq!(LogNaming::parse("stable"), Some(LogNaming::Stable));
+        assert_eq!(LogNaming::parse(" PID "), Some(LogNaming::Pid));
+        assert_eq!(LogNaming::parse("timestamp"), Some(LogNaming::Timestamp));
+        assert_eq!(LogNaming::parse("daily"), None);
+    }
+}
@@ -1,62 +1,170 @@
-mod app;
-mod global_constants;
-mod utils;
-mod ui;
-
-use crate::app::application::Application;
-use anyhow::Result;
+use mediablocker::app::application::Application;
+use mediablocker::autostart::{install_autostart, uninstall_autostart};
+use mediablocker::app::media_player::PlayerInfo;
+use mediablocker::app::screensaver::ScreensaverStatus;
+use mediablocker::cli::{Cli, USAGE};
+use mediablocker::config::{config_path, Config};
+use mediablocker::config_watcher::{apply_log_level, reload_config, watch_config};
+use mediablocker::diagnostic::{doctor, test_inhibit};
+use mediablocker::exit_status::ExitStatus;
+use mediablocker::instance::InstanceLock;
+use mediablocker::logging::{setup_logging, LogFormat, LogNaming, LogRotation};
+use mediablocker::status::print_status;
+use anyhow::{Context, Result};
 use async_std::task;
-use directories::ProjectDirs;
 use log::LevelFilter;
-use simplelog::{ColorChoice, Config, TermLogger, TerminalMode, WriteLogger};
-use std::fs::File;
+use signal_hook::consts::{SIGINT, SIGTERM, SIGUSR1};
+use signal_hook::iterator::Signals;
+use std::process::ExitCode;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
+use tao::event::StartCause;
 use tao::event_loop::{ControlFlow, EventLoopBuilder};
-use tray_icon::menu::{MenuEvent};
-use tray_icon::{TrayIconBuilder, Icon};
-use crate::ui::system_tray::SystemTrayBuilder;
+use tray_icon::menu::{CheckMenuItem, MenuEvent};
+use tray_icon::TrayIconBuilder;
+use mediablocker::ui::about_dialog::show_about_dialog;
+use mediablocker::ui::icon_pack::{find_icon_dir, parse_tint, AppIconState, IconPack};
+use mediablocker::ui::player_menu::PlayerMenu;
+use mediablocker::ui::snooze_menu::SnoozeMenu;
+use mediablocker::ui::stats_menu::StatsMenu;
+use mediablocker::ui::system_tray::SystemTrayBuilder;
+use mediablocker::utils::{capture_local_offset, open_path};
+
+// Tooltip shown when no media is keeping the screen awake
+const DEFAULT_TOOLTIP: &str = "Media Blocker";
+
+// How long each frame of the pulsing active icon is shown for
+const ICON_PULSE_INTERVAL: Duration = Duration::from_secs(1);
 
 // Define a custom event type to wake up the loop
 enum UserEvent {
     MenuEvent(MenuEvent),
-    RefreshIcon
+    RefreshIcon,
+    RefreshPlayers(Vec<PlayerInfo>),
+    Shutdown,
+    ToggleBlocker,
 }
 
-// Struct to hold our loaded tray_icons so we don't reload them from disk constantly
-struct IconPack {
-    active: Icon,
-    inactive: Icon,
-    blocked: Icon,
+fn main() -> ExitCode {
+    // The event loop exits the process itself, so only failures return here
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // Report the failure to both the terminal and the log file (if it was set up)
+            eprintln!("{:#}", e);
+            log::error!("[SYSTEM] {:#}", e);
+
+            // Exit with the code documented for the category of failure
+            ExitStatus::from_error(&e).into()
+        }
+    }
 }
 
-// Enum to track the current visual state of the icon
-#[derive(PartialEq, Clone, Copy, Debug)]
-enum AppIconState {
-    Active,
-    Inactive,
-    Blocked,
-}
+fn run() -> Result<()> {
+    // Read the options passed on the command line
+    let cli = Cli::parse();
 
-fn main() -> Result<()> {
-    // This initializes the GTK backend required by the tray-icon crate
-    if let Err(e) = gtk::init() {
-        eprintln!("Failed to initialize GTK: {}", e);
-        return Err(anyhow::anyhow!("Failed to initialize GTK"));
+    // Answer --help and --version without loading anything, so they work even if the config is broken
+    if cli.help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    if cli.version {
+        println!("MediaBlocker {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+
+    // Add or remove the launch on login, which does not need the config or the D-Bus
+    if cli.install_autostart {
+        return install_autostart();
+    }
+    if cli.uninstall_autostart {
+        return uninstall_autostart();
     }
 
+    // Read the local time zone before any threads are started, as it cannot be read safely afterwards
+    capture_local_offset();
+
+    // Load the user's settings, creating the config file if it does not exist
+    let config = Config::load().context(ExitStatus::ConfigFailed)?;
+
+    // Report the state of the blocker without starting the tray, so scripts can query it
+    if cli.status {
+        return task::block_on(print_status(&config, cli.json)).context(ExitStatus::BusConnectionFailed);
+    }
+
+    // Report what the environment supports, so users have one thing to paste into a bug report
+    if cli.doctor {
+        return task::block_on(doctor(&config));
+    }
+
+    // Check that the screen can be kept awake on this desktop, without waiting for media to play
+    if cli.test_inhibit {
+        return task::block_on(test_inhibit(&config));
+    }
+
+    // Exit before creating a second tray icon if another instance is already running
+    let instance_lock = InstanceLock::acquire()?;
+
+    // This initializes the GTK backend required by the tray-icon crate
+    gtk::init().context(ExitStatus::GtkInitFailed)?;
+
+    // Pick the log level from the command line, then the environment, then the config
+    let pinned_level_name = cli.log_level.clone().or_else(|| std::env::var("MEDIABLOCKER_LOG").ok());
+    let requested_level = pinned_level_name.clone().unwrap_or_else(|| config.log_level.clone());
+    let log_level = LevelFilter::from_str(requested_level.trim()).ok();
+
+    // A level from the command line or environment is kept when the config is reloaded
+    let pinned_level = pinned_level_name.map(|_| log_level.unwrap_or(LevelFilter::Warn));
+
+    // Pick the format of the log file from the command line, then the config
+    let requested_format = cli.log_format.clone().unwrap_or_else(|| config.log_format.clone());
+    let log_format = LogFormat::parse(&requested_format);
+
     // Setup logging to a log file
-    log::debug!("[SYSTEM] Setting up log file...");
-    let _log_path = setup_logging()?;
+    let rotation = LogRotation {
+        max_size: config.log_max_size_mb * 1024 * 1024,
+        keep_count: config.log_keep_count,
+    };
+    let file_level = config.verbose_level(log_level.unwrap_or(LevelFilter::Warn));
+    let log_naming = LogNaming::parse(&config.log_naming);
+    let log_path = setup_logging(
+        log_naming.unwrap_or(LogNaming::Stable),
+        log_format.unwrap_or(LogFormat::Text),
+        file_level,
+        rotation,
+    )
+    .context(ExitStatus::LoggingFailed)?;
+
+    // Invalid options can only be reported once the logger has been set up
+    if log_level.is_none() {
+        log::warn!("[SYSTEM] Unknown log level: {}, using warn", requested_level);
+    }
+    if log_format.is_none() {
+        log::warn!("[SYSTEM] Unknown log format: {}, using text", requested_format);
+    }
+    if log_naming.is_none() {
+        log::warn!("[SYSTEM] Unknown log naming: {}, using stable", config.log_naming);
+    }
+    for arg in &cli.unrecognised {
+        log::warn!("[SYSTEM] Ignoring unrecognised argument: {} (see --help)", arg);
+    }
 
     // Create the Application state (Async)
     log::debug!("[SYSTEM] Initializing application state...");
-    let app = task::block_on(Application::new())?;
+    let app = task::block_on(Application::new(&config)).context(ExitStatus::BusConnectionFailed)?;
 
     // Wrap the application state in ARC
     let app = Arc::new(app);
     log::info!("[SYSTEM] Application state initialized successfully");
 
+    // Apply edits to the config file as they are saved, the watcher is kept for as long as the event loop runs
+    let _config_watcher = watch_config(app.clone(), pinned_level)
+        .inspect_err(|e| log::warn!("[SYSTEM] Unable to watch the config file for changes: {}", e))
+        .ok();
+
     // Create a clone of the app for the background process
     let app_worker = app.clone();
 
@@ -77,6 +185,8 @@ fn main() -> Result<()> {
     // Create a proxy to send events from the tray handler to the menu event loop
     let menu_proxy= event_loop.create_proxy();
     let ui_proxy = menu_proxy.clone();
+    let grace_proxy = menu_proxy.clone();
+    let signal_proxy = menu_proxy.clone();
 
     // Register the menu event handler
     MenuEvent::set_event_handler(Some(move |event| {
@@ -85,16 +195,46 @@ fn main() -> Result<()> {
 
     // Listen for UI updates and forward them to the event loop
     let ui_consumer = app.get_ui_channel().get_consumer();
+    let ui_app = app.clone();
     thread::spawn(move || {
         task::block_on(async {
             // Wait for messes from the playback monitor
-            while let Ok(_) = ui_consumer.recv().await {
+            while ui_consumer.recv().await.is_ok() {
+                // Drain the refreshes queued during a signal storm, as one refresh shows the latest state
+                while ui_consumer.try_recv().is_ok() {}
+
                 // Wait up the main thread with a RefreshIcon event
                 let _ = ui_proxy.send_event(UserEvent::RefreshIcon);
+
+                // List the players here, so the main thread is not held up by the D-Bus calls
+                match ui_app.list_players().await {
+                    Ok(players) => {
+                        let _ = ui_proxy.send_event(UserEvent::RefreshPlayers(players));
+                    }
+                    Err(e) => log::warn!("[TRAY MENU] Failed to list the media players: {}", e),
+                }
             }
         })
     });
 
+    // Handle the signals sent to the process:
+    // - SIGTERM/SIGINT exit cleanly (e.g. the session logs out or the app is killed), so the inhibit is not left behind
+    // - SIGUSR1 toggles the blocker like the tray checkbox, so a hotkey can be bound with `pkill -USR1 MediaBlocker`
+    let mut signals = Signals::new([SIGTERM, SIGINT, SIGUSR1]).context("Failed to register the signal handlers")?;
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            // Forward the signal to the event loop, which owns the tray
+            let event = if signal == SIGUSR1 {
+                log::info!("[SYSTEM] Received SIGUSR1, toggling the blocker...");
+                UserEvent::ToggleBlocker
+            } else {
+                log::info!("[SYSTEM] Received signal {}, shutting down...", signal);
+                UserEvent::Shutdown
+            };
+            let _ = signal_proxy.send_event(event);
+        }
+    });
+
     // Log that the event loop has been registered
     log::info!("[EVENT LOOP] Menu event loop proxy registered successfully");
 
@@ -103,14 +243,45 @@ fn main() -> Result<()> {
     let mut tray_builder = SystemTrayBuilder::new();
 
     // Create the toggle checkbox menu item for blocking screensaver updates
-    let toggle_id = tray_builder.create_check_menu_item("Blocker Enabled", true);
+    let toggle_id = tray_builder.create_check_menu_item("Blocker Enabled", config.enabled_on_start);
+
+    // Create the toggle to keep the screen awake regardless of the media
+    let hold_id = tray_builder.create_check_menu_item("Keep Awake Now", false);
+
+    // Create the button to pause all players and disable the blocker in one click
+    let boss_id = tray_builder.create_menu_item("Boss Key");
+
+    // Create the submenu to disable the blocker for a while
+    let snooze_menu = SnoozeMenu::new(tray_builder.create_submenu("Snooze"));
 
     // Add a separator
     tray_builder.create_separator();
 
+    // Create the submenu listing the detected media players and their playback status
+    let mut player_menu = PlayerMenu::new(tray_builder.create_submenu("Media Players"));
+
+    // Create the submenu showing how long the screen has been kept awake
+    let stats_menu = StatsMenu::new(tray_builder.create_submenu("Statistics"));
+
+    // Create the button to list the media players again, in case a change was missed
+    let reload_players_id = tray_builder.create_menu_item("Reload Players");
+
+    // Create the button to list and release the inhibitors held by the app
+    let release_id = tray_builder.create_menu_item("Release Inhibitors");
+
+    // Create the buttons to edit the config file and apply the edits
+    let open_config_id = tray_builder.create_menu_item("Open Config");
+    let reload_config_id = tray_builder.create_menu_item("Reload Config");
+
     // Create the button to open the logs file
     let logs_id = tray_builder.create_menu_item("Open Logs");
 
+    // Create the checkbox to log at the debug level without restarting
+    let verbose_id = tray_builder.create_check_menu_item("Verbose Logging", config.verbose_logging);
+
+    // Create the button to show the version and backend of the app
+    let about_id = tray_builder.create_menu_item("About");
+
     // Add a separator
     tray_builder.create_separator();
 
@@ -119,50 +290,137 @@ fn main() -> Result<()> {
 
     // Build the menu
     let tray_menu = tray_builder.build();
+
+    // Keep a handle to the toggle so actions other than clicking it can keep its checkmark in sync
+    let toggle_item = tray_builder
+        .get_check_menu_item(&toggle_id)
+        .expect("Toggle menu item should be a checkbox");
+    let hold_item = tray_builder
+        .get_check_menu_item(&hold_id)
+        .expect("Keep awake menu item should be a checkbox");
+    let verbose_item = tray_builder
+        .get_check_menu_item(&verbose_id)
+        .expect("Verbose logging menu item should be a checkbox");
     log::info!("[TRAY MENU] System tray menu created successfully");
 
     // Create a system tray icon
     log::debug!("[TRAY ICON] Building system tray icon...");
 
-    // Load the icon directory
-    let icon_dir = std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/public/tray_icons"));
+    // Find the icon directory, an installed binary may not have one (the icons are then generated)
+    let icon_dir = find_icon_dir();
+    match &icon_dir {
+        Some(icon_dir) => log::info!("[TRAY ICON] Loading icons from {}", icon_dir.display()),
+        None => log::warn!("[TRAY ICON] No icon directory was found, generating the icons"),
+    }
+
+    // Read the colour to recolour the icons to, an invalid colour leaves them as they are
+    let tint = parse_tint(&config.icon_tint);
+    if tint.is_none() && !config.icon_tint.trim().is_empty() {
+        log::warn!("[TRAY ICON] Invalid icon tint: {}, using the icons as they are", config.icon_tint);
+    }
 
     // Load the tray_icons from the icon directory
-    let icons = IconPack {
-        active: load_tray_icon(&icon_dir.join("active.png")),
-        inactive: load_tray_icon(&icon_dir.join("inactive.png")),
-        blocked: load_tray_icon(&icon_dir.join("blocked.png")),
-    };
+    let icons = IconPack::load(icon_dir.as_deref(), tint).context(ExitStatus::TrayFailed)?;
 
     // Define ths system tray icon + menu
     let tray_icon = TrayIconBuilder::new()
         .with_menu(Box::new(tray_menu))
-        .with_tooltip("Media Blocker")
+        .with_tooltip(DEFAULT_TOOLTIP)
         .with_title("MediaBlocker")
-        .with_icon(icons.inactive.clone())
-        .build()?;
+        .with_icon(icons.get(AppIconState::Inactive).clone())
+        .build()
+        .context(ExitStatus::TrayFailed)?;
 
-    // Define the current icon state
+    // Define the current icon state and tooltip
     let mut current_icon_state = AppIconState::Inactive;
+    let mut current_tooltip = String::from(DEFAULT_TOOLTIP);
+    let mut current_title = String::from("MediaBlocker");
+
+    // Define when a deferred change away from the active icon is due (None if not deferred)
+    let mut icon_deadline: Option<Instant> = None;
+
+    // Define when the pulsing active icon next changes frame (None if not pulsing), and which frame is shown
+    let animate_active = config.animate_active;
+    let mut pulse_deadline: Option<Instant> = None;
+    let mut pulse_dimmed = false;
 
     // Log that the system tray icon was created successfully
     log::info!("[TRAY ICON] System tray icon created successfully");
 
-    // Get the producer channel for the system tray
-    let tray_producer = app.get_tray_channel().get_producer();
-
     // Start the event loop for the system tray menu
     log::info!("[EVENT LOOP] Starting main event loop...");
     event_loop.run(move |event, _, control_flow| {
-        // When loop iteration completes, wait until next event
-        *control_flow = ControlFlow::Wait;
+        // When loop iteration completes, wait until next event (or until a deferred icon change or pulse is due)
+        *control_flow = match icon_deadline.into_iter().chain(pulse_deadline).min() {
+            Some(deadline) => ControlFlow::WaitUntil(deadline),
+            None => ControlFlow::Wait,
+        };
 
         // Receive an event from the menu
         match event {
+            tao::event::Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {
+                let now = Instant::now();
+
+                // Switch the pulsing active icon to its other frame
+                if pulse_deadline.is_some_and(|deadline| now >= deadline) {
+                    pulse_dimmed = !pulse_dimmed;
+                    let frame = if pulse_dimmed { icons.get_pulse() } else { icons.get(AppIconState::Active) };
+                    let _ = tray_icon.set_icon(Some(frame.clone()));
+                    pulse_deadline = Some(now + ICON_PULSE_INTERVAL);
+                }
+
+                // Re-evaluate the icon once the grace period of a deferred icon change has passed
+                if icon_deadline.is_some_and(|deadline| now >= deadline) {
+                    let _ = grace_proxy.send_event(UserEvent::RefreshIcon);
+                }
+                *control_flow = match pulse_deadline {
+                    Some(deadline) => ControlFlow::WaitUntil(deadline),
+                    None => ControlFlow::Wait,
+                };
+            }
+
             // Handle UI refresh requests
             tao::event::Event::UserEvent(UserEvent::RefreshIcon) => {
+                // Describe the media keeping the screen awake, as the track can change without the icon changing
+                let new_tooltip = determine_tooltip(&app);
+                if new_tooltip != current_tooltip {
+                    let _ = tray_icon.set_tooltip(Some(&new_tooltip));
+                    current_tooltip = new_tooltip;
+                }
+
+                // Show how many players are tracked next to the icon, on panels that display the title
+                let new_title = determine_title(&app);
+                if new_title != current_title {
+                    tray_icon.set_title(Some(&new_title));
+                    current_title = new_title;
+                }
+
+                // Keep the toggle in sync, as a snooze disables and re-enables the blocker in the background
+                toggle_item.set_checked(app.get_screensaver().status().is_enabled());
+                hold_item.set_checked(app.get_screensaver().is_manually_held());
+
+                // Keep the statistics current, the periodic re-check refreshes them while the screen is kept awake
+                stats_menu.update(app.get_screensaver().get_stats().totals());
+
                 // Determine the state of the app icon
-                let new_icon_state = determine_app_icon_state(app.clone());
+                let new_icon_state = AppIconState::for_app(&app);
+
+                // Keep the active icon through brief pauses for the configured grace period, as rapid
+                // play/pause would flicker the icon
+                let grace = app.get_screensaver().unblock_grace();
+                if current_icon_state == AppIconState::Active && new_icon_state == AppIconState::Inactive && !grace.is_zero() {
+                    // Start the grace period the first time the media is seen to have stopped
+                    let deadline = *icon_deadline.get_or_insert_with(|| Instant::now() + grace);
+
+                    // Wait until the grace period has passed before changing the icon
+                    if Instant::now() < deadline {
+                        *control_flow = ControlFlow::WaitUntil(pulse_deadline.map_or(deadline, |pulse| pulse.min(deadline)));
+                        return;
+                    }
+                }
+
+                // The icon is no longer being held in the active state
+                icon_deadline = None;
 
                 // If the state has not changes
                 if new_icon_state == current_icon_state {
@@ -170,12 +428,8 @@ fn main() -> Result<()> {
                     return;
                 }
 
-                // Match on the new app icon state
-                let new_icon = match new_icon_state {
-                    AppIconState::Active => &icons.active,
-                    AppIconState::Inactive => &icons.inactive,
-                    AppIconState::Blocked => &icons.blocked,
-                };
+                // Get the icon for the new app icon state
+                let new_icon = icons.get(new_icon_state);
 
                 // Set the tray icon to be the new icon
                 let _ = tray_icon.set_icon(Some(new_icon.clone()));
@@ -183,6 +437,30 @@ fn main() -> Result<()> {
                 // Set the current icon to be the new icon
                 current_icon_state = new_icon_state;
                 log::trace!("[TRAY MENU] New icon: {:?}", new_icon_state);
+
+                // Pulse the icon while the screen is kept awake, stopping on the static icon once it is not
+                pulse_dimmed = false;
+                pulse_deadline = (animate_active && new_icon_state == AppIconState::Active)
+                    .then(|| Instant::now() + ICON_PULSE_INTERVAL);
+                if let Some(deadline) = pulse_deadline {
+                    *control_flow = ControlFlow::WaitUntil(deadline);
+                }
+            }
+
+            // Handle a request to exit from a signal
+            tao::event::Event::UserEvent(UserEvent::Shutdown) => {
+                shutdown(&app, &instance_lock);
+                *control_flow = ControlFlow::Exit;
+            }
+
+            // Handle a request to toggle the blocker from a signal
+            tao::event::Event::UserEvent(UserEvent::ToggleBlocker) => {
+                toggle_blocker(&app, &toggle_item);
+            }
+
+            // Handle changes to the list of media players
+            tao::event::Event::UserEvent(UserEvent::RefreshPlayers(players)) => {
+                player_menu.update(players);
             }
 
             // Handle menu item clicks
@@ -190,121 +468,275 @@ fn main() -> Result<()> {
                 // If the event is to exit the system try
                 if menu_event.id == quit_id {
                     log::info!("[SYSTEM TRAY] Quit request received. Exiting application...");
+                    shutdown(&app, &instance_lock);
                     *control_flow = ControlFlow::Exit;
                     return;
                 }
 
                 // If the event is to toggle the allowing/disallowing of screensaver updates
                 if menu_event.id == toggle_id {
-                    // Get the opposite state to indicate a toggle
-                    let next_state = !app.get_screensaver().are_updates_allowed();
+                    toggle_blocker(&app, &toggle_item);
+                    return;
+                }
 
+                // If the event is to keep the screen awake regardless of the media
+                if menu_event.id == hold_id {
+                    // Get the opposite state to indicate a toggle
+                    let hold = !app.get_screensaver().is_manually_held();
                     log::info!(
-                        "[SYSTEM TRAY] Toggle request received. New state: {}",
-                        if next_state { "ENABLED" } else { "DISABLED" }
+                        "[SYSTEM TRAY] Keep awake request received. New state: {}",
+                        if hold { "HELD" } else { "RELEASED" }
                     );
 
-                    // Update the state of the screensaver to match the system tray state
-                    if next_state {
-                        app.get_screensaver().allow_updates();
-                        log::debug!("[SYSTEM TRAY] Screensaver updates allowed.");
-                    } else {
-                        app.get_screensaver().disallow_updates();
-                        log::debug!("[SYSTEM TRAY] Screensaver updates disallowed.");
+                    // Take or release the hold in the background, the checkbox is synced once it is done
+                    let hold_app = app.clone();
+                    task::spawn(async move {
+                        if let Err(e) = hold_app.set_manual_hold(hold).await {
+                            log::error!("[SYSTEM TRAY] Failed to change the hold on the screen: {}", e);
+                            let _ = hold_app.get_ui_channel().request_refresh();
+                        }
+                    });
+                    return;
+                }
+
+                // If the event is to pause everything and disable the blocker
+                if menu_event.id == boss_id {
+                    log::info!("[SYSTEM TRAY] Boss key request received");
+
+                    // The blocker is disabled by the boss key, so uncheck the toggle to match
+                    toggle_item.set_checked(false);
+
+                    // Pause the players and release the inhibit in the background
+                    let boss_app = app.clone();
+                    task::spawn(async move {
+                        if let Err(e) = boss_app.boss_key().await {
+                            log::error!("[SYSTEM TRAY] Boss key failed: {}", e);
+                        }
+                    });
+                    return;
+                }
+
+                // If the event is to disable the blocker for a while
+                if let Some(duration) = snooze_menu.get_duration(&menu_event.id) {
+                    log::info!("[SYSTEM TRAY] Snooze request received");
+
+                    // The blocker is disabled while snoozed, so uncheck the toggle to match
+                    toggle_item.set_checked(false);
+
+                    // Start the snooze in the background
+                    let snooze_app = app.clone();
+                    task::spawn(async move {
+                        if let Err(e) = snooze_app.snooze(duration).await {
+                            log::error!("[SYSTEM TRAY] Failed to snooze: {}", e);
+                        }
+                    });
+                    return;
+                }
+
+                // If the event is to rediscover the media players, the icon refreshes once they are re-checked
+                if menu_event.id == reload_players_id {
+                    log::info!("[SYSTEM TRAY] Reload players request received");
+                    if let Err(e) = app.reload_players() {
+                        log::error!("[SYSTEM TRAY] Failed to reload the media players: {}", e);
                     }
+                    return;
+                }
+
+                // If the event is to list and release the inhibitors held by the app
+                if menu_event.id == release_id {
+                    log::info!("[SYSTEM TRAY] Release inhibitors request received");
+
+                    // List and release the inhibitors in the background
+                    let release_app = app.clone();
+                    task::spawn(async move {
+                        if let Err(e) = release_app.release_orphaned_inhibitors().await {
+                            log::error!("[SYSTEM TRAY] Failed to release inhibitors: {}", e);
+                        }
+                    });
+                    return;
+                }
 
-                    // Notify the background worker to adjust state accordingly
-                    log::debug!("[SYSTEM TRAY] Sending refresh signal to background worker...");
-                    if let Err(e) = task::block_on(tray_producer.send(())) {
-                        log::error!("[SYSTEM TRAY] Failed to send signal to worker: {}", e);
+                // If the event is to track or ignore one of the listed media players
+                if let Some(player) = player_menu.get_player(&menu_event.id) {
+                    log::info!("[SYSTEM TRAY] Player toggle request received for {}", player.display_name);
+
+                    // Flip whether the player is tracked, and save it in the background
+                    let player_app = app.clone();
+                    let name = player.name.clone();
+                    let tracked = !player.tracked;
+                    task::spawn(async move {
+                        if let Err(e) = player_app.set_player_tracked(&name, tracked).await {
+                            log::error!("[SYSTEM TRAY] Failed to update the tracked players: {}", e);
+                        }
+                    });
+                    return;
+                }
+
+                // If the event is to open the config file, loading it first so the defaults are written if it is missing
+                if menu_event.id == open_config_id {
+                    match Config::load().and_then(|_| config_path()) {
+                        Ok(path) => {
+                            log::info!("[SYSTEM TRAY] Opening config file: {}", path.display());
+                            open_path(&path);
+                        }
+                        Err(e) => log::error!("[SYSTEM TRAY] Failed to open the config file: {}", e),
+                    }
+                    return;
+                }
+
+                // If the event is to apply the edits made to the config file
+                if menu_event.id == reload_config_id {
+                    log::info!("[SYSTEM TRAY] Reload config request received");
+                    reload_config(&app, pinned_level);
+                    return;
+                }
+
+                // If the event is to toggle verbose logging, saving the choice so it is kept across restarts
+                if menu_event.id == verbose_id {
+                    let verbose = verbose_item.is_checked();
+                    log::info!(
+                        "[SYSTEM TRAY] Verbose logging {}",
+                        if verbose { "enabled" } else { "disabled" }
+                    );
+                    match Config::update(|config| config.verbose_logging = verbose).and_then(|_| Config::load()) {
+                        Ok(config) => apply_log_level(&config, pinned_level),
+                        Err(e) => log::error!("[SYSTEM TRAY] Failed to save the verbose logging setting: {}", e),
                     }
                     return;
                 }
 
                 // If the event is to open the log file
                 if menu_event.id == logs_id {
-                    log::error!("[SYSTEM TRAY] Opening logs button is not a defined action");
+                    log::info!("[SYSTEM TRAY] Opening log file: {}", log_path.display());
+                    open_path(&log_path);
                     return;
                 }
+
+                // Menu events are handled on the GTK thread, so the dialog can be shown directly
+                if menu_event.id == about_id {
+                    show_about_dialog(app.get_screensaver().backend_name());
+                }
             }
             _ => {}
         }
     });
 }
 
-fn determine_app_icon_state(app: Arc<Application>) -> AppIconState {
-    // Get the screensaver from the app
-    let screensaver = app.get_screensaver();
+fn toggle_blocker(app: &Application, toggle_item: &CheckMenuItem) {
+    // Get the opposite state to indicate a toggle
+    let next_state = !app.get_screensaver().status().is_enabled();
+
+    log::info!(
+        "[SYSTEM TRAY] Toggle request received. New state: {}",
+        if next_state { "ENABLED" } else { "DISABLED" }
+    );
+
+    // Keep the checkbox in sync, as the toggle can also come from a signal rather than a click
+    toggle_item.set_checked(next_state);
 
-    // Get the flags for if the screensaver's blocked/unblocked state can be updated
-    let updates_allowed = screensaver.are_updates_allowed();
+    // Toggling the blocker manually ends any snooze
+    app.cancel_snooze();
 
-    // If updates are not allowed
-    if !updates_allowed {
-        // Update the icon to be in the blocked state
-        return AppIconState::Blocked;
+    // Update the state of the screensaver to match the system tray state
+    if next_state {
+        app.get_screensaver().allow_updates();
+        log::debug!("[SYSTEM TRAY] Screensaver updates allowed.");
+    } else {
+        app.get_screensaver().disallow_updates();
+        log::debug!("[SYSTEM TRAY] Screensaver updates disallowed.");
     }
 
-    // Get the flag for if the screensave is currently being blocked
-    let is_screensaver_blocked = screensaver.is_blocked();
+    // Notify the background worker to adjust state accordingly. This is what makes
+    // re-enabling mid-playback re-inhibit straight away, rather than waiting for the
+    // next player signal. The refresh is dropped if one is already queued, so sending never waits.
+    log::debug!("[SYSTEM TRAY] Sending refresh signal to background worker...");
+    if let Err(e) = app.get_tray_channel().request_refresh() {
+        log::error!("[SYSTEM TRAY] Failed to send signal to worker: {}", e);
+    }
+}
 
-    // If the screensaver is currently being blocked
-    if is_screensaver_blocked {
-        // Update the icon to be in the active state
-        return AppIconState::Active;
+fn shutdown(app: &Application, instance_lock: &InstanceLock) {
+    // Release the inhibit before exiting, so the screensaver is not left suppressed
+    let screensaver = app.get_screensaver();
+    if let Err(e) = task::block_on(screensaver.shutdown(&app.get_connection())) {
+        log::error!("[SYSTEM] Failed to release the inhibit: {}", e);
     }
 
-    // In the screensaver is not currently being blocked
-    // Update the icon to be in the inactive state
-    AppIconState::Inactive
+    // Let the next instance start
+    instance_lock.release();
 }
 
-fn setup_logging() -> Result<std::path::PathBuf> {
-    // Match on the state for the parsing of the project directory
-    match ProjectDirs::from("com", "MediaBlocker", "MediaBlocker") {
-        Some(proj_dirs) => {
-            // Get the log directory
-            let log_dir = proj_dirs.data_dir();
-
-            // Recursively create the log directory and any parents
-            std::fs::create_dir_all(log_dir)?;
-
-            // Get the log file
-            let log_file = log_dir.join("media_blocker.log");
-
-            // Create the logger
-            simplelog::CombinedLogger::init(vec![
-                TermLogger::new(
-                    LevelFilter::Warn,
-                    Config::default(),
-                    TerminalMode::Mixed,
-                    ColorChoice::Auto,
-                ),
-                WriteLogger::new(
-                    LevelFilter::Warn,
-                    Config::default(),
-                    File::create(&log_file)?,
-                ),
-            ])?;
-
-            // Return the log file
-            Ok(log_file)
+fn determine_tooltip(app: &Application) -> String {
+    // Get the latest status of the blocker
+    let status = app.get_status();
+
+    // The status is stale while the monitors are not running
+    if !app.is_healthy() {
+        return format!("{} — Disconnected from D-Bus", DEFAULT_TOOLTIP);
+    }
+
+    // Nothing can be blocked until a supported inhibit service appears
+    if !app.get_screensaver().has_backend() {
+        return format!("{} — No inhibit service available", DEFAULT_TOOLTIP);
+    }
+
+    // Show what is keeping the screen awake, falling back to the state of the blocker
+    let mut tooltip = match &status.now_playing {
+        Some(now_playing) => format!("Blocking — {}", now_playing.describe()),
+        None if !status.blocking_players.is_empty() => {
+            format!("Blocking — {}", status.blocking_players.join(", "))
         }
-        None => Err(anyhow::anyhow!("Failed to detect project directory")),
+        None if app.get_screensaver().is_manually_held() => "Blocking — Keep Awake Now".to_string(),
+        None if app.get_screensaver().status() == ScreensaverStatus::Disabled => match app.get_snooze_deadline() {
+            Some(deadline) => {
+                // Round up, so the last minute of the snooze is not shown as 0
+                let minutes = deadline.saturating_duration_since(Instant::now()).as_secs().div_ceil(60);
+                format!("{} — Snoozed ({} min left)", DEFAULT_TOOLTIP, minutes)
+            }
+            None => format!("{} — Disabled", DEFAULT_TOOLTIP),
+        },
+        None if app.get_screensaver().status() == ScreensaverStatus::BatteryLow => {
+            format!("{} — Paused, battery is low", DEFAULT_TOOLTIP)
+        }
+        None if app.get_screensaver().status() == ScreensaverStatus::OffSchedule => {
+            format!("{} — Outside the scheduled hours", DEFAULT_TOOLTIP)
+        }
+        None => format!("{} — Idle", DEFAULT_TOOLTIP),
+    };
+
+    // Show the other players that are keeping the screen awake alongside the one described
+    if let Some(now_playing) = &status.now_playing {
+        let others: Vec<&str> = status
+            .blocking_players
+            .iter()
+            .map(String::as_str)
+            .filter(|player| *player != now_playing.player)
+            .collect();
+        if !others.is_empty() {
+            tooltip.push_str(&format!("\nAlso playing: {}", others.join(", ")));
+        }
+    }
+
+    // Show how many players are tracked, as a player that is not tracked can never keep the screen awake
+    tooltip.push_str(&format!("\nTracking: {}", describe_player_count(app.get_tracked_player_count())));
+
+    // Show how many inhibitors are active on the system, if the backend can enumerate them
+    if let Some(count) = status.active_inhibitors {
+        tooltip.push_str(&format!("\nActive inhibitors: {}", count));
     }
+
+    tooltip
 }
 
-fn load_tray_icon(path: &std::path::Path) -> Icon {
-    // Load from file
-    let (icon_rgba, icon_width, icon_height) = {
-        let image = image::open(path)
-            .expect("Failed to open icon path")
-            .into_rgba8();
-        let (width, height) = image.dimensions();
-        let rgba = image.into_raw();
-        (rgba, width, height)
-    };
+fn determine_title(app: &Application) -> String {
+    // The title only changes with the number of players and whether the screen is kept awake
+    let state = if app.get_screensaver().status().is_blocking() { "blocking" } else { "idle" };
+    format!("MediaBlocker — {}, {}", describe_player_count(app.get_tracked_player_count()), state)
+}
 
-    // Create icon from RGBA values
-    Icon::from_rgba(icon_rgba, icon_width, icon_height).expect("Failed to load icon")
+fn describe_player_count(count: usize) -> String {
+    match count {
+        1 => "1 player".to_string(),
+        count => format!("{} players", count),
+    }
 }
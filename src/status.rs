@@ -0,0 +1,83 @@
+use crate::app::inhibitors::list_inhibitors;
+use crate::app::media_player::{playing_players, PlayerFilter};
+use crate::config::Config;
+use crate::instance::InstanceLock;
+use crate::utils::{env_flag, is_kde_desktop};
+use anyhow::Result;
+use serde::Serialize;
+use zbus::Connection;
+
+// A snapshot of the state of the blocker, queried without starting the tray
+#[derive(Debug, Serialize)]
+pub struct Status {
+    /// Indicate if an instance of MediaBlocker is running
+    pub running: bool,
+
+    /// Indicate if an inhibit taken under our application name is active (None if unknown)
+    pub inhibiting: Option<bool>,
+
+    /// The names of the media players that would keep the screen awake
+    pub playing: Vec<String>,
+}
+
+impl Status {
+    pub async fn query(config: &Config) -> Result<Self> {
+        // Establish a connection to the D-Bus session
+        let conn = Connection::session().await?;
+
+        // Find the media players that would keep the screen awake, using the user's filter
+        let filter = PlayerFilter::from_config(config);
+        let playing = playing_players(&conn, &filter)
+            .await?
+            .into_iter()
+            .map(|player| player.identity)
+            .collect();
+
+        // Look for an inhibit held under our application name, the system bus is only needed by logind
+        let system_conn = Connection::system().await.ok();
+        let use_kde = env_flag("MEDIABLOCKER_KDE_QUIRKS").unwrap_or_else(is_kde_desktop);
+        let inhibiting = match list_inhibitors(&conn, system_conn.as_ref(), use_kde).await {
+            Ok(inhibitors) => Some(
+                inhibitors
+                    .iter()
+                    .any(|inhibitor| inhibitor.app_name == config.inhibit_app_name),
+            ),
+            Err(_) => None,
+        };
+
+        Ok(Self {
+            running: InstanceLock::is_held(),
+            inhibiting,
+            playing,
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        let running = if self.running { "yes" } else { "no" };
+        let inhibiting = match self.inhibiting {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "unknown",
+        };
+        let playing = if self.playing.is_empty() {
+            "none".to_string()
+        } else {
+            self.playing.join(", ")
+        };
+        format!("running: {}, inhibiting: {}, playing: {}", running, inhibiting, playing)
+    }
+}
+
+pub async fn print_status(config: &Config, json: bool) -> Result<()> {
+    // Query the state of the blocker
+    let status = Status::query(config).await?;
+
+    // Print it on one line, for people or for scripts
+    if json {
+        println!("{}", serde_json::to_string(&status)?);
+    } else {
+        println!("{}", status.describe());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,354 @@
+// Stand-ins for the desktop services, so the blocker's logic can be tested without a D-Bus session
+use crate::app::application::Application;
+use crate::app::backends::{InhibitBackend, InhibitHandle};
+use crate::app::media_player::{PlaybackSource, PlaybackStatus, PlayerFilter, PlayerStatus};
+use crate::app::screensaver::ScreensaverState;
+use crate::config::Config;
+use crate::global_constants::{KDE_POWER_MANAGEMENT_DESTINATION, KDE_POWER_MANAGEMENT_PATH, MEDIA_PLAYER_PATH};
+use async_trait::async_trait;
+use std::io::{BufRead, BufReader};
+use std::os::unix::net::UnixStream;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use zbus::connection::Builder;
+use zbus::{interface, Connection, Guid};
+
+// How long the tests wait for a reply on the private bus, so a player that does not answer fails fast
+const TEST_DBUS_TIMEOUT: Duration = Duration::from_millis(500);
+
+// A call made to the fake backend, in the order it was made
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BackendCall {
+    /// An inhibit was taken with the given reason, returning the given cookie
+    Inhibit { reason: String, cookie: u32 },
+
+    /// The inhibit with the given cookie was released
+    Uninhibit { cookie: u32 },
+}
+
+// An inhibit backend that records its calls, handing out a new cookie for each inhibit
+#[derive(Clone)]
+pub struct FakeBackend {
+    /// The calls made to the backend, shared with the test that handed the backend over
+    calls: Arc<Mutex<Vec<BackendCall>>>,
+
+    /// Indicate if the next inhibits fail, as if the service did not reply
+    failing: Arc<AtomicBool>,
+
+    /// The cookie handed out for the first inhibit, counting up from it for the next inhibits
+    first_cookie: u32,
+}
+
+impl Default for FakeBackend {
+    fn default() -> Self {
+        Self::with_first_cookie(1)
+    }
+}
+
+impl FakeBackend {
+    pub fn with_first_cookie(first_cookie: u32) -> Self {
+        // A first cookie of 0 checks that 0 is treated as a valid cookie, as the desktop services can return it
+        Self {
+            calls: Arc::new(Mutex::new(Vec::new())),
+            failing: Arc::new(AtomicBool::new(false)),
+            first_cookie,
+        }
+    }
+
+    pub fn calls(&self) -> Vec<BackendCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    pub fn inhibit_count(&self) -> usize {
+        self.calls().iter().filter(|call| matches!(call, BackendCall::Inhibit { .. })).count()
+    }
+
+    pub fn uninhibit_count(&self) -> usize {
+        self.calls().iter().filter(|call| matches!(call, BackendCall::Uninhibit { .. })).count()
+    }
+
+    pub fn set_failing(&self, failing: bool) {
+        self.failing.store(failing, Ordering::SeqCst);
+    }
+}
+
+#[async_trait]
+impl InhibitBackend for FakeBackend {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    async fn inhibit(&self, _conn: &Connection, _app_name: &str, reason: &str) -> anyhow::Result<InhibitHandle> {
+        if self.failing.load(Ordering::SeqCst) {
+            anyhow::bail!("The fake backend is failing");
+        }
+
+        let mut calls = self.calls.lock().unwrap();
+        let inhibits = calls.iter().filter(|call| matches!(call, BackendCall::Inhibit { .. })).count();
+        let cookie = self.first_cookie + inhibits as u32;
+        calls.push(BackendCall::Inhibit { reason: reason.to_string(), cookie });
+        Ok(InhibitHandle::Cookie(cookie))
+    }
+
+    async fn uninhibit(&self, _conn: &Connection, handle: InhibitHandle) -> anyhow::Result<()> {
+        let InhibitHandle::Cookie(cookie) = handle else {
+            anyhow::bail!("The fake backend only hands out cookies");
+        };
+        self.calls.lock().unwrap().push(BackendCall::Uninhibit { cookie });
+        Ok(())
+    }
+}
+
+// A playback source whose playing and paused players are set by the test (every other player is stopped),
+// counting how often it is queried
+#[derive(Clone, Default)]
+pub struct FakePlayback {
+    /// The bus names of the players currently playing
+    playing: Arc<Mutex<Vec<String>>>,
+
+    /// The bus names of the players currently paused
+    paused: Arc<Mutex<Vec<String>>>,
+
+    /// How many times the players have been queried
+    queries: Arc<AtomicUsize>,
+}
+
+impl FakePlayback {
+    pub fn set_playing(&self, players: &[&str]) {
+        *self.playing.lock().unwrap() = players.iter().map(|player| player.to_string()).collect();
+    }
+
+    pub fn set_paused(&self, players: &[&str]) {
+        *self.paused.lock().unwrap() = players.iter().map(|player| player.to_string()).collect();
+    }
+
+    pub fn queries(&self) -> usize {
+        self.queries.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl PlaybackSource for FakePlayback {
+    async fn playing_players(
+        &self,
+        _conn: &Connection,
+        _media_players: Option<Vec<String>>,
+    ) -> anyhow::Result<Vec<PlayerStatus>> {
+        self.queries.fetch_add(1, Ordering::SeqCst);
+        let playing = self.playing.lock().unwrap().clone();
+        Ok(playing
+            .into_iter()
+            .map(|name| PlayerStatus { identity: name.clone(), name, status: PlaybackStatus::Playing })
+            .collect())
+    }
+
+    async fn player_status(&self, _conn: &Connection, player: &str) -> PlaybackStatus {
+        if self.playing.lock().unwrap().iter().any(|playing| playing == player) {
+            PlaybackStatus::Playing
+        } else if self.paused.lock().unwrap().iter().any(|paused| paused == player) {
+            PlaybackStatus::Paused
+        } else {
+            PlaybackStatus::Stopped
+        }
+    }
+
+    fn invalidate(&self) {}
+}
+
+pub fn fake_app(conn: Connection, backend: Option<FakeBackend>, playback: &FakePlayback) -> Arc<Application> {
+    // Build the application around the fakes, with the default config and no system bus
+    let config = Config::default();
+    let backend = backend.map(|backend| Box::new(backend) as Box<dyn InhibitBackend>);
+    let screensaver = ScreensaverState::for_tests(backend, &config, Box::new(playback.clone()));
+    let filter = Arc::new(RwLock::new(PlayerFilter::from_config(&config)));
+    Arc::new(Application::from_parts(&config, conn, None, screensaver, filter))
+}
+
+pub async fn p2p_connection() -> anyhow::Result<(Connection, Connection)> {
+    // Connect two ends of a socket pair, for code that needs a connection but never calls through it
+    let (server, client) = UnixStream::pair()?;
+    let server = Builder::async_io_unix_stream(server).server(Guid::generate())?.p2p().build();
+    let client = Builder::async_io_unix_stream(client).p2p().build();
+    Ok(futures::try_join!(server, client)?)
+}
+
+// A D-Bus session of its own, so the tests neither see nor disturb the players of the desktop they run on
+pub struct PrivateBus {
+    /// The dbus-daemon serving the session, killed once the bus is dropped
+    daemon: Child,
+
+    /// The address the daemon listens on
+    address: String,
+}
+
+impl PrivateBus {
+    pub fn start() -> anyhow::Result<Self> {
+        // Start a session daemon in the foreground, which prints its address once it is listening
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let mut address = String::new();
+        if let Some(stdout) = daemon.stdout.take() {
+            BufReader::new(stdout).read_line(&mut address)?;
+        }
+        Ok(Self { daemon, address: address.trim().to_string() })
+    }
+
+    pub async fn connect(&self) -> anyhow::Result<Connection> {
+        Ok(Builder::address(self.address.as_str())?.method_timeout(TEST_DBUS_TIMEOUT).build().await?)
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+// The root MPRIS interface of a fake player, naming it
+struct FakePlayerRoot {
+    identity: String,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl FakePlayerRoot {
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        self.identity.clone()
+    }
+}
+
+// The MPRIS Player interface of a fake player, reporting the playback status set by the test
+struct FakePlayerControls {
+    status: String,
+
+    /// How long the player takes to answer for its playback status
+    delay: Duration,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl FakePlayerControls {
+    #[zbus(property)]
+    async fn playback_status(&self) -> String {
+        async_std::task::sleep(self.delay).await;
+        self.status.clone()
+    }
+
+    fn pause(&mut self) {
+        self.status = "Paused".to_string();
+    }
+}
+
+// The MPRIS Player interface of a hung player, which never answers for its playback status
+struct HungPlayerControls;
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl HungPlayerControls {
+    #[zbus(property)]
+    async fn playback_status(&self) -> String {
+        futures::future::pending().await
+    }
+}
+
+// A media player on the private bus, which is removed from the bus once dropped
+pub struct FakePlayer {
+    conn: Connection,
+}
+
+impl FakePlayer {
+    pub async fn serve(bus: &PrivateBus, name: &str, identity: &str, status: &str) -> anyhow::Result<Self> {
+        Self::serve_slow(bus, name, identity, status, Duration::ZERO).await
+    }
+
+    pub async fn serve_slow(
+        bus: &PrivateBus,
+        name: &str,
+        identity: &str,
+        status: &str,
+        delay: Duration,
+    ) -> anyhow::Result<Self> {
+        let controls = FakePlayerControls { status: status.to_string(), delay };
+        let conn = Builder::address(bus.address.as_str())?
+            .serve_at(MEDIA_PLAYER_PATH, FakePlayerRoot { identity: identity.to_string() })?
+            .serve_at(MEDIA_PLAYER_PATH, controls)?
+            .name(name.to_string())?
+            .build()
+            .await?;
+        Ok(Self { conn })
+    }
+
+    pub async fn serve_hung(bus: &PrivateBus, name: &str) -> anyhow::Result<Self> {
+        let conn = Builder::address(bus.address.as_str())?
+            .serve_at(MEDIA_PLAYER_PATH, HungPlayerControls)?
+            .name(name.to_string())?
+            .build()
+            .await?;
+        Ok(Self { conn })
+    }
+
+    pub async fn set_status(&self, status: &str) -> anyhow::Result<()> {
+        // Change the status, announcing it as a real player would
+        let controls = self
+            .conn
+            .object_server()
+            .interface::<_, FakePlayerControls>(MEDIA_PLAYER_PATH)
+            .await?;
+        controls.get_mut().await.status = status.to_string();
+        controls.get().await.playback_status_changed(controls.signal_emitter()).await?;
+        Ok(())
+    }
+}
+
+// KDE's PowerManagement policy agent, handing out the cookie 0 (a valid cookie) for every inhibition
+struct FakePolicyAgent {
+    /// How many inhibitions have been added
+    added: Arc<AtomicUsize>,
+
+    /// The cookies of the inhibitions that have been released, in the order they were released
+    released: Arc<Mutex<Vec<u32>>>,
+}
+
+#[interface(name = "org.kde.Solid.PowerManagement.PolicyAgent")]
+impl FakePolicyAgent {
+    fn add_inhibition(&self, _policies: u32, _app_name: String, _reason: String) -> u32 {
+        self.added.fetch_add(1, Ordering::SeqCst);
+        0
+    }
+
+    fn release_inhibition(&self, cookie: u32) {
+        self.released.lock().unwrap().push(cookie);
+    }
+}
+
+// KDE's PowerManagement service on the private bus, which is removed from the bus once dropped
+pub struct FakeKdePowerManagement {
+    _conn: Connection,
+    added: Arc<AtomicUsize>,
+    released: Arc<Mutex<Vec<u32>>>,
+}
+
+impl FakeKdePowerManagement {
+    pub async fn serve(bus: &PrivateBus) -> anyhow::Result<Self> {
+        let (added, released) = (Arc::new(AtomicUsize::new(0)), Arc::new(Mutex::new(Vec::new())));
+        let agent = FakePolicyAgent { added: added.clone(), released: released.clone() };
+        let conn = Builder::address(bus.address.as_str())?
+            .serve_at(KDE_POWER_MANAGEMENT_PATH, agent)?
+            .name(KDE_POWER_MANAGEMENT_DESTINATION)?
+            .build()
+            .await?;
+        Ok(Self { _conn: conn, added, released })
+    }
+
+    pub fn added(&self) -> usize {
+        self.added.load(Ordering::SeqCst)
+    }
+
+    pub fn released(&self) -> Vec<u32> {
+        self.released.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,27 @@
+use crate::global_constants::REPOSITORY_URL;
+use gtk::prelude::*;
+use gtk::{ButtonsType, DialogFlags, MessageDialog, MessageType, Window};
+
+pub fn show_about_dialog(backend: Option<&str>) {
+    // Describe the version and the backend used to keep the screen awake
+    let text = format!(
+        "MediaBlocker {}\n\nInhibit backend: {}\n{}",
+        env!("CARGO_PKG_VERSION"),
+        backend.unwrap_or("none"),
+        REPOSITORY_URL
+    );
+
+    // The tray has no window of its own, so the dialog is shown without a parent
+    let dialog = MessageDialog::new(
+        None::<&Window>,
+        DialogFlags::empty(),
+        MessageType::Info,
+        ButtonsType::Close,
+        &text,
+    );
+    dialog.set_title("About MediaBlocker");
+
+    // Close the dialog without blocking the event loop while it is open
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.show_all();
+}
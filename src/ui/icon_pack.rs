@@ -0,0 +1,378 @@
+use crate::app::application::Application;
+use crate::app::screensaver::ScreensaverStatus;
+use anyhow::Context;
+use gtk::prelude::*;
+use image::RgbaImage;
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{Options, Tree};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tray_icon::Icon;
+
+// The size in pixels of the icons generated for states that have no icon file
+const GENERATED_ICON_SIZE: u32 = 32;
+
+// The size in pixels SVG icons are rasterized to, large enough for HiDPI panels which scale it down
+const SVG_ICON_SIZE: u32 = 128;
+
+// Enum to track the current visual state of the icon
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum AppIconState {
+    Active,
+    Inactive,
+    Blocked,
+    Paused,
+    Disconnected,
+}
+
+impl AppIconState {
+    /// Every icon state, ordered so that fallback states are resolved before the states using them
+    pub const ALL: [AppIconState; 5] = [
+        AppIconState::Inactive,
+        AppIconState::Active,
+        AppIconState::Blocked,
+        AppIconState::Paused,
+        AppIconState::Disconnected,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AppIconState::Active => "active",
+            AppIconState::Inactive => "inactive",
+            AppIconState::Blocked => "blocked",
+            AppIconState::Paused => "paused",
+            AppIconState::Disconnected => "disconnected",
+        }
+    }
+
+    pub fn colour(&self) -> [u8; 4] {
+        // The RGBA colour of each state, matching the colours of the shipped icons
+        match self {
+            AppIconState::Active => [76, 175, 80, 255],
+            AppIconState::Inactive => [255, 152, 0, 255],
+            AppIconState::Blocked => [244, 67, 54, 255],
+            AppIconState::Paused => [91, 127, 214, 255],
+            AppIconState::Disconnected => [158, 158, 158, 255],
+        }
+    }
+
+    pub fn fallback(&self) -> Option<AppIconState> {
+        // The inactive icon is the base every other state falls back to, so it has no fallback
+        match self {
+            AppIconState::Inactive => None,
+            AppIconState::Active | AppIconState::Blocked | AppIconState::Paused => Some(AppIconState::Inactive),
+            AppIconState::Disconnected => Some(AppIconState::Blocked),
+        }
+    }
+
+    fn fallback_among(&self, available: &HashSet<AppIconState>) -> Option<AppIconState> {
+        // Walk the fallbacks until one has an icon, as the next fallback may be missing too
+        let mut fallback = self.fallback();
+        while let Some(state) = fallback {
+            if available.contains(&state) {
+                return Some(state);
+            }
+            fallback = state.fallback();
+        }
+        None
+    }
+
+    pub fn for_app(app: &Application) -> Self {
+        // If a monitor has failed, the other states would be stale
+        if !app.is_healthy() {
+            return AppIconState::Disconnected;
+        }
+
+        // Without an inhibit backend the screen cannot be kept awake, whatever the state
+        if !app.get_screensaver().has_backend() {
+            return AppIconState::Disconnected;
+        }
+
+        // Map a consistent snapshot of the screensaver's state to its icon
+        match app.get_screensaver().status() {
+            // The user has turned the blocker off, so show the paused icon
+            ScreensaverStatus::Disabled => AppIconState::Paused,
+
+            // The screensaver is currently being blocked, so show the active icon
+            ScreensaverStatus::Blocking => AppIconState::Active,
+
+            // The screensaver is not currently being blocked, so show the inactive icon
+            ScreensaverStatus::Idle => AppIconState::Inactive,
+
+            // The battery is too low to keep the screen awake, so show the blocked icon
+            ScreensaverStatus::BatteryLow => AppIconState::Blocked,
+
+            // It is outside the scheduled hours, so show the blocked icon
+            ScreensaverStatus::OffSchedule => AppIconState::Blocked,
+        }
+    }
+}
+
+// Struct to hold our loaded tray_icons so we don't reload them from disk constantly
+pub struct IconPack {
+    /// The icon to display for each of the states
+    icons: HashMap<AppIconState, Icon>,
+
+    /// The dimmed active icon, alternated with the active icon to pulse while the screen is kept awake
+    pulse: Icon,
+}
+
+impl IconPack {
+    pub fn load(icon_dir: Option<&Path>, tint: Option<[u8; 3]>) -> anyhow::Result<Self> {
+        let mut images = HashMap::new();
+
+        // The states whose icon was loaded from a file, as only those are worth reusing for another state
+        let mut from_file = HashSet::new();
+
+        // Load the icon for each of the states from the icon directory
+        for state in AppIconState::ALL {
+            // Each state is stored as an SVG or a PNG named after the state, preferring the SVG as it scales
+            let path = icon_dir.and_then(|icon_dir| {
+                ["svg", "png"]
+                    .into_iter()
+                    .map(|extension| icon_dir.join(format!("{}.{}", state.name(), extension)))
+                    .find(|path| path.exists())
+            });
+
+            // If the icon for the state exists, load it (rasterizing an SVG once, here)
+            if let Some(path) = path {
+                images.insert(state, load_icon_image(&path, tint)?);
+                from_file.insert(state);
+                continue;
+            }
+
+            // Otherwise, reuse the icon of the nearest fallback state that has a file of its own
+            let image = match state.fallback_among(&from_file) {
+                Some(fallback) => {
+                    log::warn!(
+                        "[TRAY ICON] No icon for the {} state, using the {} icon instead",
+                        state.name(),
+                        fallback.name()
+                    );
+                    images[&fallback].clone()
+                }
+                None => {
+                    // Without any icon to reuse, draw one in the colour of the state so the tray still works
+                    log::warn!("[TRAY ICON] No icon for the {} state, generating one", state.name());
+                    generate_icon_image(state)
+                }
+            };
+            images.insert(state, image);
+        }
+
+        // Dim the active icon for the second frame of the pulse
+        let pulse = to_tray_icon(dim_icon_image(&images[&AppIconState::Active]))?;
+
+        // Convert the images to icons, which cannot be read back
+        let icons = images
+            .into_iter()
+            .map(|(state, image)| Ok((state, to_tray_icon(image)?)))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { icons, pulse })
+    }
+
+    pub fn get(&self, state: AppIconState) -> &Icon {
+        // Every state is resolved when loading, so the icon always exists
+        &self.icons[&state]
+    }
+
+    pub fn get_pulse(&self) -> &Icon {
+        &self.pulse
+    }
+}
+
+pub fn parse_tint(value: &str) -> Option<[u8; 3]> {
+    // Match the tint to the theme, light icons on a dark panel and dark icons on a light one
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("auto") {
+        return Some(if prefers_dark_theme() { [255, 255, 255] } else { [32, 32, 32] });
+    }
+
+    // Otherwise the tint is a hex colour (an empty value keeps the icons as they are)
+    let hex = value.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn prefers_dark_theme() -> bool {
+    // GTK must be initialised to read its settings, a missing setting is treated as a light theme
+    let Some(settings) = gtk::Settings::default() else {
+        return false;
+    };
+    let theme_name = settings.gtk_theme_name().unwrap_or_default();
+    settings.is_gtk_application_prefer_dark_theme() || theme_name.to_lowercase().contains("dark")
+}
+
+pub fn find_icon_dir() -> Option<PathBuf> {
+    // An explicit directory takes priority, e.g. for packagers or custom icons
+    let explicit = std::env::var_os("MEDIABLOCKER_ICON_DIR").map(PathBuf::from);
+
+    // Then the data directories a system install places the icons in ($XDG_DATA_HOME, then $XDG_DATA_DIRS)
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    let installed = data_home
+        .into_iter()
+        .chain(data_dirs.split(':').map(PathBuf::from))
+        .map(|data_dir| data_dir.join("mediablocker/icons"));
+
+    // Then next to the executable, for a portable install or one under a custom prefix
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf));
+    let beside_exe = exe_dir
+        .into_iter()
+        .flat_map(|exe_dir| [exe_dir.join("icons"), exe_dir.join("../share/mediablocker/icons")]);
+
+    // Finally the icons in the source tree, for running a development build
+    let source = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/public/tray_icons"));
+
+    // Use the first directory that exists
+    explicit
+        .into_iter()
+        .chain(installed)
+        .chain(beside_exe)
+        .chain(std::iter::once(source))
+        .find(|dir| dir.is_dir())
+}
+
+fn generate_icon_image(state: AppIconState) -> RgbaImage {
+    // Draw a filled circle in the colour of the state, leaving the corners transparent
+    let size = GENERATED_ICON_SIZE;
+    let radius = size as f32 / 2.0;
+    RgbaImage::from_fn(size, size, |x, y| {
+        let (dx, dy) = (x as f32 + 0.5 - radius, y as f32 + 0.5 - radius);
+        if dx * dx + dy * dy <= radius * radius {
+            image::Rgba(state.colour())
+        } else {
+            image::Rgba([0, 0, 0, 0])
+        }
+    })
+}
+
+fn load_icon_image(path: &Path, tint: Option<[u8; 3]>) -> anyhow::Result<RgbaImage> {
+    // SVG icons are drawn at the size of the icon, rather than decoded
+    let mut image = if path.extension().is_some_and(|extension| extension == "svg") {
+        RgbaImage::from_raw(SVG_ICON_SIZE, SVG_ICON_SIZE, rasterize_svg(path, SVG_ICON_SIZE)?)
+            .context("The rasterized SVG does not match the size of the icon")?
+    } else {
+        // Load from file
+        image::open(path)?.into_rgba8()
+    };
+
+    // Recolour the icon, keeping its transparency so the shape of a monochrome icon is kept
+    if let Some(tint) = tint {
+        for pixel in image.pixels_mut() {
+            pixel.0[..3].copy_from_slice(&tint);
+        }
+    }
+
+    Ok(image)
+}
+
+fn dim_icon_image(image: &RgbaImage) -> RgbaImage {
+    // Halve the opacity of every pixel, so the icon fades without changing its shape or colour
+    let mut dimmed = image.clone();
+    for pixel in dimmed.pixels_mut() {
+        pixel.0[3] /= 2;
+    }
+    dimmed
+}
+
+fn to_tray_icon(image: RgbaImage) -> anyhow::Result<Icon> {
+    // Create icon from RGBA values
+    let (width, height) = image.dimensions();
+    Ok(Icon::from_rgba(image.into_raw(), width, height)?)
+}
+
+fn rasterize_svg(path: &Path, size: u32) -> anyhow::Result<Vec<u8>> {
+    // Parse the SVG
+    let data = std::fs::read(path)?;
+    let tree = Tree::from_data(&data, &Options::default())
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    // Scale the SVG to fit a square of the given size, keeping its aspect ratio
+    let mut pixmap = Pixmap::new(size, size).context("Failed to allocate the icon")?;
+    let scale = size as f32 / tree.size().width().max(tree.size().height());
+    resvg::render(&tree, Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+
+    // The pixmap is premultiplied, but the icon expects straight RGBA values
+    let rgba = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let colour = pixel.demultiply();
+            [colour.red(), colour.green(), colour.blue(), colour.alpha()]
+        })
+        .collect();
+    Ok(rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fake_app, p2p_connection, FakeBackend, FakePlayback};
+    use async_std::task;
+
+    #[test]
+    fn paused_falls_back_to_inactive() {
+        assert_eq!(AppIconState::Paused.fallback(), Some(AppIconState::Inactive));
+    }
+
+    #[test]
+    fn fallback_skips_states_without_an_icon() {
+        // Without a blocked icon, the disconnected state keeps walking to the inactive icon
+        let available = HashSet::from([AppIconState::Inactive]);
+        assert_eq!(AppIconState::Disconnected.fallback_among(&available), Some(AppIconState::Inactive));
+
+        // The nearest fallback is used when it has an icon
+        let available = HashSet::from([AppIconState::Inactive, AppIconState::Blocked]);
+        assert_eq!(AppIconState::Disconnected.fallback_among(&available), Some(AppIconState::Blocked));
+    }
+
+    #[test]
+    fn fallback_is_none_without_any_icon() {
+        for state in AppIconState::ALL {
+            assert_eq!(state.fallback_among(&HashSet::new()), None);
+        }
+    }
+
+    #[test]
+    fn every_fallback_is_resolved_before_its_state() {
+        // IconPack::load only reuses icons already loaded, so each fallback must come earlier in ALL
+        let position = |state| AppIconState::ALL.iter().position(|other| *other == state).unwrap();
+        for state in AppIconState::ALL {
+            if let Some(fallback) = state.fallback() {
+                assert!(position(fallback) < position(state), "{:?} is listed before {:?}", state, fallback);
+            }
+        }
+    }
+
+    #[test]
+    fn icon_shows_the_error_state_without_a_backend() {
+        task::block_on(async {
+            let (_server, conn) = p2p_connection().await.unwrap();
+            let app = fake_app(conn, None, &FakePlayback::default());
+            assert_eq!(AppIconState::for_app(&app), AppIconState::Disconnected);
+        });
+    }
+
+    #[test]
+    fn icon_follows_the_blocker_with_a_backend() {
+        task::block_on(async {
+            let (_server, conn) = p2p_connection().await.unwrap();
+            let app = fake_app(conn, Some(FakeBackend::default()), &FakePlayback::default());
+            assert_eq!(AppIconState::for_app(&app), AppIconState::Inactive);
+
+            app.get_screensaver().disallow_updates();
+            assert_eq!(AppIconState::for_app(&app), AppIconState::Paused);
+        });
+    }
+}
@@ -1 +1,6 @@
-pub mod system_tray;
\ No newline at end of file
+pub mod about_dialog;
+pub mod icon_pack;
+pub mod player_menu;
+pub mod snooze_menu;
+pub mod stats_menu;
+pub mod system_tray;
@@ -0,0 +1,72 @@
+use crate::app::media_player::{PlaybackStatus, PlayerInfo};
+use std::collections::HashMap;
+use tray_icon::menu::{CheckMenuItem, MenuId, MenuItem, Submenu};
+
+pub struct PlayerMenu {
+    /// The submenu listing the media players
+    submenu: Submenu,
+
+    /// The media players that the submenu currently lists
+    players: Vec<PlayerInfo>,
+
+    /// The bus names of the media players, keyed by the ID of their entry
+    entries: HashMap<MenuId, String>,
+}
+
+impl PlayerMenu {
+    pub fn new(submenu: Submenu) -> Self {
+        // Start with the placeholder, until the first list of players arrives
+        let mut menu = Self {
+            submenu,
+            players: Vec::new(),
+            entries: HashMap::new(),
+        };
+        menu.rebuild();
+        menu
+    }
+
+    pub fn update(&mut self, players: Vec<PlayerInfo>) {
+        // Rebuilding the submenu is visible to the user, so only do it when the list has changed
+        if players == self.players {
+            return;
+        }
+
+        self.players = players;
+        self.rebuild();
+    }
+
+    pub fn get_player(&self, id: &MenuId) -> Option<&PlayerInfo> {
+        // Find the player for the entry, which is gone if the player closed since the list was built
+        let name = self.entries.get(id)?;
+        self.players.iter().find(|player| &player.name == name)
+    }
+
+    fn rebuild(&mut self) {
+        // Remove the entries of the previous list
+        while self.submenu.remove_at(0).is_some() {}
+        self.entries.clear();
+
+        // Show a placeholder rather than an empty submenu when no players are detected
+        if self.players.is_empty() {
+            let _ = self.submenu.append(&MenuItem::new("No media players detected", false, None));
+            return;
+        }
+
+        // Add an entry showing the status and name of each player, checked if it can keep the screen awake
+        for player in &self.players {
+            let text = format!("{} {}", status_symbol(player.status), player.display_name);
+            let item = CheckMenuItem::new(text, true, player.tracked, None);
+            self.entries.insert(item.id().clone(), player.name.clone());
+            let _ = self.submenu.append(&item);
+        }
+    }
+}
+
+fn status_symbol(status: PlaybackStatus) -> &'static str {
+    match status {
+        PlaybackStatus::Playing => "▶",
+        PlaybackStatus::Paused => "⏸",
+        PlaybackStatus::Stopped => "⏹",
+        PlaybackStatus::Unknown => "?",
+    }
+}
@@ -0,0 +1,29 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use tray_icon::menu::{MenuId, MenuItem, Submenu};
+
+// The snooze lengths offered in the submenu, in minutes
+const SNOOZE_MINUTES: [u64; 3] = [15, 30, 60];
+
+pub struct SnoozeMenu {
+    /// The length of each snooze, keyed by the ID of its entry
+    entries: HashMap<MenuId, Duration>,
+}
+
+impl SnoozeMenu {
+    pub fn new(submenu: Submenu) -> Self {
+        // Add an entry for each of the snooze lengths
+        let mut entries = HashMap::new();
+        for minutes in SNOOZE_MINUTES {
+            let item = MenuItem::new(format!("Snooze {} min", minutes), true, None);
+            entries.insert(item.id().clone(), Duration::from_secs(minutes * 60));
+            let _ = submenu.append(&item);
+        }
+
+        Self { entries }
+    }
+
+    pub fn get_duration(&self, id: &MenuId) -> Option<Duration> {
+        self.entries.get(id).copied()
+    }
+}
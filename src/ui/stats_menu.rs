@@ -0,0 +1,27 @@
+use crate::app::stats::{format_duration, StatsTotals};
+use tray_icon::menu::{MenuItem, Submenu};
+
+pub struct StatsMenu {
+    /// The entry showing the time kept awake today
+    today: MenuItem,
+
+    /// The entry showing the time kept awake this session
+    session: MenuItem,
+}
+
+impl StatsMenu {
+    pub fn new(submenu: Submenu) -> Self {
+        // The entries only show the totals, so they cannot be clicked
+        let today = MenuItem::new("Today: 0m", false, None);
+        let session = MenuItem::new("This Session: 0m", false, None);
+        let _ = submenu.append(&today);
+        let _ = submenu.append(&session);
+
+        Self { today, session }
+    }
+
+    pub fn update(&self, totals: StatsTotals) {
+        self.today.set_text(format!("Today: {}", format_duration(totals.today)));
+        self.session.set_text(format!("This Session: {}", format_duration(totals.session)));
+    }
+}
@@ -1,5 +1,6 @@
-use tray_icon::menu::{CheckMenuItem, IsMenuItem, Menu, MenuId, MenuItem, PredefinedMenuItem};
+use tray_icon::menu::{CheckMenuItem, IsMenuItem, Menu, MenuId, MenuItem, MenuItemKind, PredefinedMenuItem, Submenu};
 
+#[derive(Default)]
 pub struct SystemTrayBuilder {
     // The items for the system tray menu
     items: Vec<Box<dyn IsMenuItem>>,
@@ -26,6 +27,14 @@ impl SystemTrayBuilder {
         self.add_item(item)
     }
 
+    pub fn create_submenu(&mut self, title: &str) -> Submenu {
+        // Create the submenu, its items are added through the returned handle
+        let submenu = Submenu::new(title, true);
+        // Add the submenu to the menu
+        self.add_item(submenu.clone());
+        submenu
+    }
+
     pub fn create_separator(&mut self) -> MenuId {
         // Create the separator menu item
         let item = PredefinedMenuItem::separator();
@@ -33,6 +42,17 @@ impl SystemTrayBuilder {
         self.add_item(item)
     }
 
+    pub fn get_check_menu_item(&self, id: &MenuId) -> Option<CheckMenuItem> {
+        // Find the checkbox menu item with the matching ID
+        self.items
+            .iter()
+            .filter(|item| item.id() == id)
+            .find_map(|item| match item.kind() {
+                MenuItemKind::Check(check_item) => Some(check_item),
+                _ => None,
+            })
+    }
+
     pub fn build(&self) -> Menu {
         // Create a new menu
         let menu = Menu::new();
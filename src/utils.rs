@@ -1,9 +1,159 @@
+use crate::app::media_player::{PlaybackStatus, PlayerFilter};
+use std::sync::OnceLock;
+use time::{OffsetDateTime, UtcOffset};
+
+// The offset of the local time zone, captured while the process has one thread (None if not captured)
+static LOCAL_OFFSET: OnceLock<UtcOffset> = OnceLock::new();
+
 pub fn is_media_player(name: &str) -> bool {
+    // MPRIS names are "org.mpris.MediaPlayer2" or "org.mpris.MediaPlayer2.<player>", so the prefix must end on a
+    // segment boundary (otherwise "org.mpris.MediaPlayer2Bogus" would match)
     static FILTER: &str = "org.mpris.MediaPlayer2";
-    name.starts_with(FILTER)
+    name.strip_prefix(FILTER)
+        .is_some_and(|rest| rest.is_empty() || rest.strip_prefix('.').is_some_and(|player| !player.is_empty()))
+}
+
+pub fn should_track_player(name: &str, filter: &PlayerFilter) -> bool {
+    // Only media players can be tracked
+    if !is_media_player(name) {
+        return false;
+    }
+
+    // An allowlist takes priority, otherwise every player not on the denylist is tracked
+    let matches = |entry: &String| player_matches(name, entry);
+    if !filter.allowlist.is_empty() {
+        filter.allowlist.iter().any(matches)
+    } else {
+        !filter.denylist.iter().any(matches)
+    }
+}
+
+pub fn player_matches(name: &str, entry: &str) -> bool {
+    // Match the entry against the name of the player, ignoring any instance suffix (e.g. "firefox.instance_1_23")
+    let player = player_display_name(name);
+    let entry = entry.trim();
+    player.eq_ignore_ascii_case(entry)
+        || player
+            .to_lowercase()
+            .starts_with(&format!("{}.", entry.to_lowercase()))
+}
+
+pub fn is_audio_only_url(url: &str) -> bool {
+    // Streaming services for music identify their tracks with their own URI scheme (e.g. "spotify:track:...")
+    let url = url.trim().to_lowercase();
+    if url.starts_with("spotify:") {
+        return true;
+    }
+
+    // Otherwise, judge local files and direct links by their file extension (ignoring any query string)
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let extension = path.rsplit_once('.').map(|(_, extension)| extension).unwrap_or_default();
+    matches!(
+        extension,
+        "mp3" | "flac" | "ogg" | "oga" | "opus" | "m4a" | "aac" | "wav" | "wma" | "alac" | "aiff"
+    )
+}
+
+pub fn player_base_name(name: &str) -> &str {
+    // Strip any instance suffix, so "org.mpris.MediaPlayer2.firefox.instance_1_23" is named "firefox"
+    let display_name = player_display_name(name);
+    display_name.split('.').next().unwrap_or(display_name)
+}
+
+pub fn player_display_name(name: &str) -> &str {
+    // Strip the MPRIS prefix so "org.mpris.MediaPlayer2.spotify" displays as "spotify"
+    name.strip_prefix("org.mpris.MediaPlayer2.").unwrap_or(name)
+}
+
+pub fn parse_playback_status(status: &str) -> PlaybackStatus {
+    // Ignore any whitespace or quotes surrounding the value
+    let status = status.trim().trim_matches('"');
+
+    // Match the exact MPRIS values, as a substring match would accept unrelated values
+    if status.eq_ignore_ascii_case("Playing") {
+        PlaybackStatus::Playing
+    } else if status.eq_ignore_ascii_case("Paused") {
+        PlaybackStatus::Paused
+    } else if status.eq_ignore_ascii_case("Stopped") {
+        PlaybackStatus::Stopped
+    } else {
+        PlaybackStatus::Unknown
+    }
+}
+
+pub fn env_flag(name: &str) -> Option<bool> {
+    // Read a boolean override from the environment, ignoring unset or unrecognised values
+    match std::env::var(name).ok()?.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+pub fn capture_local_offset() {
+    // The local offset can only be read safely while the process has a single thread, so read it once at startup
+    if let Ok(offset) = UtcOffset::current_local_offset() {
+        let _ = LOCAL_OFFSET.set(offset);
+    }
+}
+
+pub fn local_now() -> OffsetDateTime {
+    // Fall back to UTC if the local offset could not be captured
+    let now = OffsetDateTime::now_utc();
+    match LOCAL_OFFSET.get() {
+        Some(offset) => now.to_offset(*offset),
+        None => now,
+    }
 }
 
-pub fn is_playback_running(status: &str) -> bool {
-    // Return if the status is running
-    status.to_lowercase().contains("playing")
+pub fn is_kde_desktop() -> bool {
+    // XDG_CURRENT_DESKTOP is a colon separated list of desktop names (e.g. "KDE" or "ubuntu:KDE")
+    std::env::var("XDG_CURRENT_DESKTOP")
+        .map(|desktops| desktops.split(':').any(|desktop| desktop.eq_ignore_ascii_case("KDE")))
+        .unwrap_or(false)
+}
+
+pub fn open_path(path: &std::path::Path) {
+    // Open the path with the user's default application, without waiting for it to close
+    match std::process::Command::new("xdg-open").arg(path).spawn() {
+        Ok(mut child) => {
+            log::debug!("[SYSTEM] Opened {}", path.display());
+
+            // Reap the launcher once it exits, so it is not left behind as a zombie process
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => log::error!("[SYSTEM] Failed to open {}: {}", path.display(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn media_players_are_named_under_the_mpris_prefix() {
+        assert!(is_media_player("org.mpris.MediaPlayer2.vlc"));
+        assert!(is_media_player("org.mpris.MediaPlayer2.spotify.instance123"));
+        assert!(is_media_player("org.mpris.MediaPlayer2"));
+        assert!(!is_media_player("org.mpris.MediaPlayer2."));
+        assert!(!is_media_player("org.mpris.MediaPlayer2Bogus"));
+        assert!(!is_media_player("org.freedesktop.ScreenSaver"));
+    }
+
+    #[test]
+    fn playback_status_matches_the_exact_mpris_values() {
+        let cases = [
+            ("\"Playing\"", PlaybackStatus::Playing),
+            ("Playing", PlaybackStatus::Playing),
+            ("\"Paused\"", PlaybackStatus::Paused),
+            ("\"Stopped\"", PlaybackStatus::Stopped),
+            ("\"playing\"", PlaybackStatus::Playing),
+            ("\"NotPlaying\"", PlaybackStatus::Unknown),
+            ("", PlaybackStatus::Unknown),
+            ("garbage", PlaybackStatus::Unknown),
+        ];
+        for (status, expected) in cases {
+            assert_eq!(parse_playback_status(status), expected, "{}", status);
+        }
+    }
 }